@@ -36,18 +36,7 @@ STORE_TX_HASH=$(
   \"token_address\": \"secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx\",  \
   \"token_hash\": \"CD400FB73F5C99EDBC6AAB22C2593332B8C9F2EA806BF9B42E3A523F3AD06F62\",  \
   \"token_vk\": \"123\",  \
-  \"tier1_entry_fee\": \"1000000\", \
-  \"tier1_triggerer_fee\": \"500000\", \
-  \"tier1_min_entries\": 2, \
-  \"tier1_max_rand_number\": 2, \
-  \"tier2_entry_fee\": \"500000\", \
-  \"tier2_triggerer_fee\": \"250000\", \
-  \"tier2_min_entries\": 2, \
-  \"tier2_max_rand_number\": 2, \
-  \"tier3_entry_fee\": \"250000\", \
-  \"tier3_triggerer_fee\": \"175000\", \
-  \"tier3_min_entries\": 2, \
-  \"tier3_max_rand_number\": 2 \
+  \"reveal_timeout\": 100  \
   } \
   " --from test1 --gas 1500000 --label LuckyNumber_$label -b block -y |
   jq -r .txhash
@@ -60,18 +49,8 @@ STORE_TX_HASH=$(
   \"triggerer_address\": \"secret1v5y7as75cqd0trtq62hgzj7u4ck9slhnrf3k4c\",  \
   \"token_address\": \"secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx\",  \
   \"token_hash\": \"CD400FB73F5C99EDBC6AAB22C2593332B8C9F2EA806BF9B42E3A523F3AD06F62\",  \
-  \"tier1_entry_fee\": \"10000000\", \
-  \"tier1_triggerer_fee\": \"5000000\", \
-  \"tier1_min_entries\": 4, \
-  \"tier1_max_rand_number\": 4, \
-  \"tier2_entry_fee\": \"5000000\", \
-  \"tier2_triggerer_fee\": \"2500000\", \
-  \"tier2_min_entries\": 3, \
-  \"tier2_max_rand_number\": 3, \
-  \"tier3_entry_fee\": \"1000000\", \
-  \"tier3_triggerer_fee\": \"500000\", \
-  \"tier3_min_entries\": 2, \
-  \"tier3_max_rand_number\": 2 \
+  \"token_vk\": \"123\",  \
+  \"reveal_timeout\": 100  \
   } \
   " --from test1 --gas 1500000 --label LuckyNumber_$label -b block -y |
   jq -r .txhash
@@ -83,30 +62,40 @@ contract_address=$(secretcli query compute list-contract-by-code $code_id | jq '
 echo "contract_address: '$contract_address'"
 contract_address_without_quotes=$(echo $contract_address | tr -d '"')
 
+# create pools
+secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "12h Lucky Number", "description": "1 - 30", "display_order": 3, "entry_fee": "1000000", "triggerer_fee_bps": 500, "min_triggerer_fee": "500000", "max_triggerer_fee": "2000000", "min_entries": 2, "max_rand_number": 2, "round_duration": 43200, "betting_cutoff": 600, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 20, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": "lucky_number", "jackpot": {"max_carry_over": "50000000", "overflow": "secondary_prize"}}}' --from test1 -y --gas 1500000 -b block
+secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "1h Lucky Number", "description": "1 - 15", "display_order": 2, "entry_fee": "500000", "triggerer_fee_bps": 500, "min_triggerer_fee": "250000", "max_triggerer_fee": "1000000", "min_entries": 2, "max_rand_number": 2, "round_duration": 3600, "betting_cutoff": 300, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 20, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": "lucky_number"}}' --from test1 -y --gas 1500000 -b block
+secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "5 min Lucky Number", "description": "1 - 5", "display_order": 1, "entry_fee": "250000", "triggerer_fee_bps": 500, "min_triggerer_fee": "175000", "max_triggerer_fee": "700000", "min_entries": 2, "max_rand_number": 2, "round_duration": 300, "betting_cutoff": 30, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 20, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": "lucky_number"}}' --from test1 -y --gas 1500000 -b block
+secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "Daily Lotto", "description": "3 of 20", "display_order": 4, "entry_fee": "1000000", "triggerer_fee_bps": 500, "min_triggerer_fee": "500000", "max_triggerer_fee": "2000000", "min_entries": 2, "max_rand_number": 20, "round_duration": 86400, "betting_cutoff": 600, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 20, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": {"lotto": {"picks": 3, "brackets": [{"matches": 3, "share_bps": 6000}, {"matches": 2, "share_bps": 3000}]}}}}' --from test1 -y --gas 1500000 -b block
+secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "1h Raffle", "description": "One winner", "display_order": 5, "entry_fee": "500000", "triggerer_fee_bps": 500, "min_triggerer_fee": "250000", "max_triggerer_fee": "1000000", "min_entries": 2, "max_rand_number": 1, "round_duration": 3600, "betting_cutoff": 300, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 1, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": "raffle"}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"create_pool":{"name": "Weekly No Loss", "description": "Yield raffle", "display_order": 6, "entry_fee": "1000000", "triggerer_fee_bps": 500, "min_triggerer_fee": "100000", "max_triggerer_fee": "400000", "min_entries": 2, "max_rand_number": 1, "round_duration": 604800, "betting_cutoff": 600, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 1, "max_tickets_per_user": 1, "house_fee_bps": 0, "pool_type": {"no_loss": {"yield_source": "<yield source address>", "yield_source_hash": "<yield source code hash>"}}}}' --from test1 -y --gas 1500000 -b block
+
 sleep 10
 
-msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 1}}')
+msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [1]}}')
 secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": '$contract_address', "amount": "1000000", "msg": "'"$msg"'"}}' --from test1 -y --gas 1500000 -b block
 
-msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 2}}')
+msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [2]}}')
 secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": '$contract_address', "amount": "1000000", "msg": "'"$msg"'"}}' --from test2 -y --gas 1500000 -b block
 
-msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 3}}')
+msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [3]}}')
 secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": '$contract_address', "amount": "1000000", "msg": "'"$msg"'"}}' --from test3 -y --gas 1500000 -b block
 
-msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 4}}')
+msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [4]}}')
 secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": '$contract_address', "amount": "1000000", "msg": "'"$msg"'"}}' --from test4 -y --gas 1500000 -b block
 
-msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 5}}')
+msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [5]}}')
 secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": '$contract_address', "amount": "1000000", "msg": "'"$msg"'"}}' --from test5 -y --gas 1500000 -b block
 
 sleep 5
 
 # Get Rounds
-#secretcli q compute query $contract_address_without_quotes '{"get_rounds": {"tier1": true, "tier2": true, "tier3": true, "page_size": 10, "page": 0 }}' | base64 --decode --ignore-garbage
+#secretcli q compute query $contract_address_without_quotes '{"get_pools": {}}' | base64 --decode --ignore-garbage
+#secretcli q compute query $contract_address_without_quotes '{"get_paginated_rounds": {"pools": [1, 2, 3], "page_size": 10, "page": 0 }}' | base64 --decode --ignore-garbage
 
-# trigger
-#secretcli tx compute execute $contract_address_without_quotes '{"trigger_lucky_number":{"tier1": true, "tier2": true, "tier3": true, "entropy": 1234}}' --from test1 -y --gas 1500000 -b block
+# commit and trigger (the reveal has to be in a later block than the commit)
+#secretcli tx compute execute $contract_address_without_quotes '{"commit_entropy":{"pools": [1, 2, 3], "entropy_commitment": "'$(echo -n 1234 | sha256sum | cut -d' ' -f1 | xxd -r -p | base64)'"}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"trigger_lucky_number":{"pools": [1, 2, 3], "entropy": "1234"}}' --from test1 -y --gas 1500000 -b block
 
 # create VK
 #secretcli tx compute execute $contract_address_without_quotes '{"create_viewing_key":{"entropy": "1231231"}}' --from test2 -y --gas 1500000 -b block
@@ -115,25 +104,35 @@ sleep 5
 #secretcli q compute query $contract_address_without_quotes '{"get_user_bets": {"user_address": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9", "viewing_key": "/Sgx2v+2e/IJ7eIJcdRLbDg91mz3be6ZjuA0lTqWLdM="}}' | base64 --decode --ignore-garbage
 
 # withdraw
-#secretcli tx compute execute $contract_address_without_quotes '{"withdrawl":{"tier": 3, "round": 0}}' --from test2 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"withdrawl":{"pool": 3, "round": 0}}' --from test2 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"claim_refund":{"pool": 3, "round": 0}}' --from test2 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"claim_all":{}}' --from test2 -y --gas 1500000 -b block
+#secretcli tx compute execute $contract_address_without_quotes '{"migrate_user_bets":{"users":["secret1..."]}}' --from test2 -y --gas 1500000 -b block
 
 #secretcli q compute query secret1v9w7798n4dv9rphcl6983az53ywzrzwtuzz8ry '{"get_user_bets": {"user_address": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9"}}' | base64 --decode --ignore-garbage
 
-#secretcli q compute query secret1vkrgphn45944uekp7fn9hf5qzgzxpvq2h0mern '{"get_rounds": {"tier1": true, "tier2": true, "tier3": true, "page_size": 10, "page": 0 }}' | base64 --decode --ignore-garbage
+#secretcli q compute query secret1vkrgphn45944uekp7fn9hf5qzgzxpvq2h0mern '{"get_paginated_rounds": {"pools": [1, 2, 3], "page_size": 10, "page": 0 }}' | base64 --decode --ignore-garbage
+
+#secretcli tx compute execute secret1m4ez6yw8yr68fv8a8p744qqqpug6plnx9d4eny '{"withdrawl":{"pool": 3, "round": 0}}' --from test1 -y --gas 1500000 -b block
 
-#secretcli tx compute execute secret1m4ez6yw8yr68fv8a8p744qqqpug6plnx9d4eny '{"withdrawl":{"tier": 3, "round": 0}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute secret1m4ez6yw8yr68fv8a8p744qqqpug6plnx9d4eny '{"withdraw_deposit":{"pool": 6, "amount": "1000000"}}' --from test1 -y --gas 1500000 -b block
+#secretcli q compute query secret1m4ez6yw8yr68fv8a8p744qqqpug6plnx9d4eny '{"get_deposit": {"pool": 6, "user_address": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9", "viewing_key": "api_key_..."}}' | base64 --decode --ignore-garbage
 
-#msg=$(base64 -w 0 <<<'{"bet": {"tier": 3, "number": 1}}')
+#msg=$(base64 -w 0 <<<'{"bet": {"pool": 3, "numbers": [1]}}')
 #secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"send":{"recipient": "secret1nfycy4kdphekvdd75nvgu535wjg5jwjdakq5ec", "amount": "1000000", "msg": "'"$msg"'"}}' --from test1 -y --gas 1500000 -b block
 
 #secretcli q compute query  secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"balance":{"address": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9", "key": "api_key_IwYF2GwgPAawIp7JgJJAJKE7uW/Sj/VVJDodcOSWsZQ="}}'
 
-#secretcli tx compute execute secret10jzqsnqm88nlxzvpq3c8feg7yaft7a73np8t5v '{"trigger_lucky_number":{"tier1": true, "tier2": true, "tier3": true, "entropy": 1234}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute secret10jzqsnqm88nlxzvpq3c8feg7yaft7a73np8t5v '{"trigger_lucky_number":{"pools": [1, 2, 3], "entropy": "1234"}}' --from test1 -y --gas 1500000 -b block
 
 #secretcli q compute query secret10jzqsnqm88nlxzvpq3c8feg7yaft7a73np8t5v '{"check_triggers":{}}' | base64 --decode --ignore-garbage
 
 #secretcli tx compute execute secret1s7c6xp9wltthk5r6mmavql4xld5me3g37guhsx '{"redeem":{"amount": "1000000"}}' --from test1 -y --gas 1500000 -b block
 
 #secretcli tx compute execute secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"change_triggerer":{"triggerer": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9"}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"change_treasury":{"treasury": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9"}}' --from test1 -y --gas 1500000 -b block
+#secretcli tx compute execute secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"withdraw_treasury":{}}' --from test1 -y --gas 1500000 -b block
+#secretcli q compute query secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"get_treasury":{}}' | base64 --decode --ignore-garbage
+#secretcli tx compute execute secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"change_trigger_mode":{"trigger_mode": "permissionless"}}' --from test1 -y --gas 1500000 -b block
 #secretcli tx compute execute secret1jhcvug7afjyqfuf465p7vf900rvl838nwnc04u '{"change_admin":{"admin": "secret1kw78ltg8380qdrag6puknyk0stdhh4nj68aqj9"}}' --from test1 -y --gas 1500000 -b block
-#secretcli tx compute execute secret16d05wj83d63pgdl2tg2qmyymmpx9chs3m4pq67 '{"change_tier":{"tier": 3,"entry_fee": "100000", "triggerer_fee": "50000", "min_entries": 2, "max_rand_number": 5}}' --from test1 -y --gas 1500000 -b block
\ No newline at end of file
+#secretcli tx compute execute secret16d05wj83d63pgdl2tg2qmyymmpx9chs3m4pq67 '{"change_tier":{"pool": 3,"entry_fee": "100000", "triggerer_fee_bps": 500, "min_triggerer_fee": "50000", "max_triggerer_fee": "200000", "min_entries": 2, "max_rand_number": 5, "round_duration": 300, "betting_cutoff": 30, "round_expiry": 86400, "auto_distribute": true, "max_auto_winners": 20, "max_tickets_per_user": 5, "house_fee_bps": 200, "pool_type": "lucky_number"}}' --from test1 -y --gas 1500000 -b block
\ No newline at end of file
@@ -1,92 +1,77 @@
-use std::{collections::HashMap, hash::Hash, path::Prefix};
-
-use cosmwasm_std::{Api, Binary, CanonicalAddr, CosmosMsg, Empty, Env, Extern, HandleResponse, HandleResult, HumanAddr, InitResponse, LogAttribute, Querier, QueryResult, ReadonlyStorage, StdError, StdResult, Storage, Uint128, WasmMsg, from_binary, to_binary};
+use cosmwasm_std::{Api, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse, HumanAddr, InitResponse, LogAttribute, log, Querier, ReadonlyStorage, StdError, StdResult, Storage, Uint128, from_binary, to_binary};
 use cosmwasm_storage::{PrefixedStorage, ReadonlyPrefixedStorage};
-use rand::Rng;
-use rand_chacha::ChaChaRng;
-use secret_toolkit::{snip20::{self, transfer_msg}, storage::{AppendStore, AppendStoreMut, TypedStore}};
+use secret_toolkit::{snip20::{self, balance_query, transfer_msg}, storage::{AppendStore, AppendStoreMut}, utils::space_pad};
 use sha2::{Digest, Sha256};
-use rand_core::SeedableRng;
-use crate::{msg::{CountResponse, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, ResponseStatus, Snip20Msg, TierConfig}, rand::sha_256, state::{RoundStruct, UserBetStruct, UserBetsStruct, load, may_load, save}, viewing_key::{VIEWING_KEY_SIZE, ViewingKey}};
-
-/*
-    5 min Lucky Number =>  1 sSCRT => 1 - 5
-    1h Lucky Number =>  5 sSCRT => 1 - 15
-    12h Lucky Number =>  10 sSCRT => 1-30
-*/
+use crate::{error::{ContractError, ContractResult}, permit::{Permission, Permit}, msg::{HandleAnswer, HandleMsg, InitMsg, JackpotOverflow, PoolDrawSummary, PoolInfo, PoolMetadata, PoolRound, PoolTierConfig, PoolTrigger, PoolType, PrizeBracket, QueryAnswer, QueryMsg, QueryWithPermit, ResponseStatus, Snip20Msg, TierConfig, TriggerMode}, rand::{DRAW_VERSION, draw_lucky_numbers, draw_seed, draw_ticket, sha_256}, state::{BracketResult, RoundStruct, RoundTicket, UserBetStruct, load, may_load, remove, save}, viewing_key::{VIEWING_KEY_SIZE, ViewingKey}};
+
 pub const CONFIG_DATA: &[u8] = b"config";
 pub const PREFIX_VIEW_KEY: &[u8] = b"viewingkey";
-pub const LUCKY_NUMBER_CONFIG_TIER_1: &[u8] = b"tier1";
-pub const LUCKY_NUMBER_CONFIG_TIER_2: &[u8] = b"tier2";
-pub const LUCKY_NUMBER_CONFIG_TIER_3: &[u8] = b"tier3";
+// revoked permit names by owner
+pub const PREFIX_REVOKED_PERMITS: &[u8] = b"revoked_permits";
+pub const POOLS_CONFIG: &[u8] = b"pools";
+pub const POOLS_METADATA: &[u8] = b"pools_metadata";
 pub const ROUNDS_STATE: &[u8] = b"rounds";
-pub const BETS: &[u8] = b"bets";
+// bets by owner, in the order they were made
+pub const USER_BETS: &[u8] = b"user_bets";
+// positions on the owner bets by pool and round
+pub const USER_ROUND_BETS: &[u8] = b"user_round_bets";
+// positions on the owner bets by bet key
+pub const USER_BET_KEYS: &[u8] = b"user_bet_keys";
+pub const ROUND_BETTORS: &[u8] = b"round_bettors";
+pub const ROUND_TICKETS: &[u8] = b"round_tickets";
+// no-loss deposits by pool and owner
+pub const DEPOSITS: &[u8] = b"deposits";
+// no-loss depositors holding tickets by pool
+pub const DEPOSITORS: &[u8] = b"depositors";
+// positions on the pool depositors by owner
+pub const DEPOSITOR_POSITIONS: &[u8] = b"depositor_positions";
 pub const BLOCK_SIZE: usize = 256;
+pub const MAX_CLAIM_ALL_BETS: u32 = 100;
+pub const MAX_RAND_NUMBER: i16 = 1000;
+// a year in seconds, bounds round_duration and round_expiry so the round times can't overflow
+pub const MAX_ROUND_DURATION: u64 = 31_536_000;
+pub const MAX_AUTO_WINNERS: u32 = 100;
+// about a week of blocks
+pub const MAX_REVEAL_TIMEOUT: u64 = 100_000;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    if msg.reveal_timeout == 0 || msg.reveal_timeout > MAX_REVEAL_TIMEOUT {
+        return Err(StdError::generic_err(format!(
+            "Reveal timeout must be between 1 and {} blocks!", MAX_REVEAL_TIMEOUT
+        )));
+    }
+
     let prng_seed: Vec<u8> = sha_256(base64::encode(msg.entropy.clone().to_string()).as_bytes()).to_vec();
-    let addition_entropy: Vec<u64> = Vec::new();
-
-    let mut new_round: RoundStruct = RoundStruct {
-        round_number: 0,
-        lucky_number: None,
-        users_count: 0,
-        round_end_timestamp: None,
-        round_end_pool_size: None,
-        pool_size: Uint128(0),
-        users_picked_numbers_count: vec![0; 0],
-        winner_users_count: None
-    };
 
     let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
     save(&mut config_data, b"owner", &deps.api.canonical_address(&env.message.sender)?)?;
     save(&mut config_data, b"triggerer", &msg.triggerer_address)?;
     save(&mut config_data, b"token_address", &msg.token_address)?;
     save(&mut config_data, b"token_hash", &msg.token_hash)?;
+    save(&mut config_data, b"token_vk", &msg.token_vk)?;
+    // queries have no env, permits are checked against the stored address and chain
+    save(&mut config_data, b"contract_address", &env.contract.address)?;
+    save(&mut config_data, b"chain_id", &env.block.chain_id)?;
     save(&mut config_data, b"entropy", &prng_seed)?;
-    save(&mut config_data, b"base_entropy", &msg.entropy.clone().to_be_bytes())?;
-    save(&mut config_data, b"addition_entropy", &addition_entropy)?;
-
-    let mut tier1_state = PrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_1, &mut deps.storage);
-    save(&mut tier1_state, b"entry_fee", &msg.tier1_entry_fee)?;
-    save(&mut tier1_state, b"triggerer_fee", &msg.tier1_triggerer_fee)?;
-    save(&mut tier1_state, b"min_entries", &msg.tier1_min_entries)?;
-    save(&mut tier1_state, b"max_rand_number", &msg.tier1_max_rand_number)?;
-    let mut tier1_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &mut deps.storage);
-    let mut tier1_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier1_rounds)?;
-    new_round.users_picked_numbers_count = vec![0; *(&msg.tier1_max_rand_number) as usize];
-    tier1_rounds_store.push(&new_round)?;
-
-    let mut tier2_state = PrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_2, &mut deps.storage);
-    save(&mut tier2_state, b"entry_fee", &msg.tier2_entry_fee)?;
-    save(&mut tier2_state, b"triggerer_fee", &msg.tier2_triggerer_fee)?;
-    save(&mut tier2_state, b"min_entries", &msg.tier2_min_entries)?;
-    save(&mut tier2_state, b"max_rand_number", &msg.tier2_max_rand_number)?;
-    let mut tier2_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &mut deps.storage);
-    let mut tier2_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier2_rounds)?;
-    new_round.users_picked_numbers_count = vec![0; *(&msg.tier2_max_rand_number) as usize];
-    tier2_rounds_store.push(&new_round)?;
-
-    let mut tier3_state = PrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_3, &mut deps.storage);
-    save(&mut tier3_state, b"entry_fee", &msg.tier3_entry_fee)?;
-    save(&mut tier3_state, b"triggerer_fee", &msg.tier3_triggerer_fee)?;
-    save(&mut tier3_state, b"min_entries", &msg.tier3_min_entries)?;
-    save(&mut tier3_state, b"max_rand_number", &msg.tier3_max_rand_number)?;
-    let mut tier3_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &mut deps.storage);
-    let mut tier3_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier3_rounds)?;
-    new_round.users_picked_numbers_count = vec![0; *(&msg.tier3_max_rand_number) as usize];
-    tier3_rounds_store.push(&new_round)?;
+    save(&mut config_data, b"base_entropy", &msg.entropy.to_be_bytes())?;
+    save(&mut config_data, b"entropy_accumulator", &sha_256(&prng_seed))?;
+    save(&mut config_data, b"pools_count", &0u32)?;
+    save(&mut config_data, b"reveal_timeout", &msg.reveal_timeout)?;
+    save(&mut config_data, b"trigger_mode", &msg.trigger_mode.unwrap_or(TriggerMode::Triggerer))?;
+    save(&mut config_data, b"treasury", &msg.treasury)?;
+    save(&mut config_data, b"treasury_accrued", &Uint128(0))?;
+    save(&mut config_data, b"treasury_collected", &Uint128(0))?;
 
     Ok(InitResponse {
         messages: vec![
            snip20::register_receive_msg(
             env.contract_code_hash.clone(),
             None,
-            1,
+            BLOCK_SIZE,
             msg.token_hash.clone(),
             msg.token_address.clone(),
         )?,
@@ -108,32 +93,62 @@ pub fn handle<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    match msg {
+    let response = match msg {
         // Users
         HandleMsg::CreateViewingKey { entropy, .. } => try_create_key(deps, env, &entropy),
         HandleMsg::SetViewingKey { key, .. } => try_set_key(deps, env, &key),
+        HandleMsg::RevokePermit { permit_name, .. } => try_revoke_permit(deps, env, &permit_name),
 
         // Bet
         HandleMsg::Receive { sender, from, amount, msg } => try_receive(deps, env, sender, from, amount, msg),
-        HandleMsg::Withdrawl { tier, round } => try_withdrawl(deps, env, tier, round),
+        HandleMsg::Withdrawl { pool, round, .. } => try_withdrawl(deps, env, pool, round),
+        HandleMsg::ClaimRefund { pool, round, .. } => try_claim_refund(deps, env, pool, round),
+        HandleMsg::WithdrawDeposit { pool, amount, .. } => try_withdraw_deposit(deps, env, pool, amount),
+        HandleMsg::ClaimAll { start, limit, .. } => try_claim_all(deps, env, start, limit),
 
         // Triggerer
-        HandleMsg::TriggerLuckyNumber { tier1, tier2, tier3, entropy } => try_trigger_lucky_number(deps, env, tier1, tier2, tier3, entropy),
+        HandleMsg::CommitEntropy { pools, entropy_commitment } => try_commit_entropy(deps, env, pools, entropy_commitment),
+        HandleMsg::TriggerLuckyNumber { pools, entropy } => try_trigger_lucky_number(deps, env, pools, entropy),
         
         // Admin
         HandleMsg::ChangeAdmin { admin } => try_change_admin(deps, env, admin),
         HandleMsg::ChangeTriggerer { triggerer } => try_change_triggerer(deps, env, triggerer),
-        HandleMsg::ChangeTier { tier, entry_fee, triggerer_fee, min_entries, max_rand_number } => try_change_tier(deps, env, tier, entry_fee, triggerer_fee, min_entries, max_rand_number),
+        HandleMsg::ChangeTriggerMode { trigger_mode } => try_change_trigger_mode(deps, env, trigger_mode),
+        HandleMsg::ChangeTreasury { treasury } => try_change_treasury(deps, env, treasury),
+        HandleMsg::WithdrawTreasury {} => try_withdraw_treasury(deps, env),
+        HandleMsg::CreatePool { name, description, display_order, entry_fee, triggerer_fee_bps, min_triggerer_fee, max_triggerer_fee, min_entries, max_rand_number, round_duration, betting_cutoff, round_expiry, auto_distribute, max_auto_winners, max_tickets_per_user, pool_type, jackpot, house_fee_bps } => try_create_pool(deps, env, PoolMetadata { name, description, display_order }, TierConfig { entry_fee, triggerer_fee_bps, min_triggerer_fee, max_triggerer_fee, min_entries, max_rand_number, round_duration, betting_cutoff, round_expiry, auto_distribute, max_auto_winners, max_tickets_per_user, pool_type, jackpot, house_fee_bps }),
+        HandleMsg::ChangePoolMetadata { pool, name, description, display_order } => try_change_pool_metadata(deps, env, pool, PoolMetadata { name, description, display_order }),
+        HandleMsg::ChangeTier { pool, entry_fee, triggerer_fee_bps, min_triggerer_fee, max_triggerer_fee, min_entries, max_rand_number, round_duration, betting_cutoff, round_expiry, auto_distribute, max_auto_winners, max_tickets_per_user, pool_type, jackpot, house_fee_bps } => try_change_tier(deps, env, pool, TierConfig { entry_fee, triggerer_fee_bps, min_triggerer_fee, max_triggerer_fee, min_entries, max_rand_number, round_duration, betting_cutoff, round_expiry, auto_distribute, max_auto_winners, max_tickets_per_user, pool_type, jackpot, house_fee_bps }),
+
+        _ => Err(StdError::generic_err("Handler not found!").into())
+    };
+    pad_handle_result(response.map_err(StdError::from), BLOCK_SIZE)
+}
 
-        _ => Err(StdError::generic_err("Handler not found!"))
-    }
+/// Space pads the response data to a multiple of block_size, so its size doesn't tell apart the outcomes of a handle
+fn pad_handle_result(response: StdResult<HandleResponse>, block_size: usize) -> StdResult<HandleResponse> {
+    response.map(|mut response| {
+        response.data = response.data.map(|mut data| {
+            space_pad(block_size, &mut data.0);
+            data
+        });
+        response
+    })
+}
+
+/// Space pads the query answer to a multiple of block_size
+fn pad_query_result(response: StdResult<Binary>, block_size: usize) -> StdResult<Binary> {
+    response.map(|mut response| {
+        space_pad(block_size, &mut response.0);
+        response
+    })
 }
 
 fn try_create_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     entropy: &str,
-) -> HandleResult {
+) -> ContractResult<HandleResponse> {
     // create and store the key
     let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
     let entropy_base: Vec<u8> = load(&config_data, b"entropy")?;
@@ -142,9 +157,11 @@ fn try_create_key<S: Storage, A: Api, Q: Querier>(
     let mut key_store = PrefixedStorage::new(PREFIX_VIEW_KEY, &mut deps.storage);
     save(&mut key_store, message_sender.as_slice(), &key.to_hashed())?;
 
+    accumulate_entropy(&mut deps.storage, &env, &env.message.sender, entropy.as_bytes())?;
+
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
+        log: vec![log("action", "create_viewing_key")],
         data: Some(to_binary(&HandleAnswer::ViewingKey {
             key: format!("{}", key),
         })?),
@@ -154,9 +171,9 @@ fn try_create_key<S: Storage, A: Api, Q: Querier>(
 fn try_set_key<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    key: &String,
-) -> HandleResult {
-    let vk = ViewingKey(key.clone());
+    key: &str,
+) -> ContractResult<HandleResponse> {
+    let vk = ViewingKey(key.to_string());
     let message_sender = &deps.api.canonical_address(&env.message.sender)?;
 
     let mut key_store = PrefixedStorage::new(PREFIX_VIEW_KEY, &mut deps.storage);
@@ -164,7 +181,26 @@ fn try_set_key<S: Storage, A: Api, Q: Querier>(
 
     Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
+        log: vec![log("action", "set_viewing_key")],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None
+        })?),
+    })
+}
+
+fn try_revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: &String,
+) -> ContractResult<HandleResponse> {
+    let message_sender = deps.api.canonical_address(&env.message.sender)?;
+    let mut revoked_permits = PrefixedStorage::multilevel(&[PREFIX_REVOKED_PERMITS, message_sender.as_slice()], &mut deps.storage);
+    save(&mut revoked_permits, permit_name.as_bytes(), &true)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "revoke_permit"), log("permit_name", permit_name)],
         data: Some(to_binary(&HandleAnswer::Status {
             status: ResponseStatus::Success,
             message: None
@@ -179,23 +215,19 @@ pub fn try_receive<S: Storage, A: Api, Q: Querier>(
     from: HumanAddr,
     amount: Uint128,
     msg: Binary,
-) -> StdResult<HandleResponse> {
+) -> ContractResult<HandleResponse> {
         let msg: HandleMsg = from_binary(&msg)?; 
 
-        if let HandleMsg::Bet {tier,number} = msg.clone() {
-            let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
+        if let HandleMsg::Bet {pool,numbers,entropy, ..} = msg.clone() {
+            let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
             let token_address: HumanAddr = load(&config_data, b"token_address")?;
             if env.message.sender != token_address {
-                return Err(StdError::generic_err(format!(
-                    "Invalid token sent!"
-                )));
+                Err(StdError::generic_err("Invalid token sent!").into())
             } else {
-                return try_bet(deps, env.clone(), amount, from, number, tier)
+                try_bet(deps, env.clone(), amount, from, numbers, pool, entropy)
             }
         } else {
-            return Err(StdError::generic_err(format!(
-                "Receive handler not found!"
-            )));
+            Err(StdError::generic_err("Receive handler not found!").into())
          }
 }
 
@@ -204,107 +236,240 @@ pub fn try_bet<S: Storage, A: Api, Q: Querier>(
     env: Env,
     amount: Uint128,
     from: HumanAddr,
-    number: i16,
-    tier: i8
-) -> StdResult<HandleResponse> {
+    numbers: Vec<i16>,
+    pool: u32,
+    entropy: Option<String>
+) -> ContractResult<HandleResponse> {
     let user_address = deps.api.canonical_address(&from)?;
 
-    // check if amount is correct for this tier
-    let tier_config_key = 
-        if tier == 1 { LUCKY_NUMBER_CONFIG_TIER_1 } 
-        else if tier == 2 { LUCKY_NUMBER_CONFIG_TIER_2 } 
-        else if tier == 3 { LUCKY_NUMBER_CONFIG_TIER_3 } 
-        else { 
-            return Err(StdError::generic_err(format!(
-                "Tier invalid"
-            )));
-        };
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
 
-    // check correct entry fee for the tier selected    
-    let tier_config = ReadonlyPrefixedStorage::new(tier_config_key, &deps.storage);
-    let entry_fee_tier: Uint128 = load(&tier_config, b"entry_fee")?;
-    if entry_fee_tier != amount {
-        return Err(StdError::generic_err(format!(
-            "Amount invalid of tier choosen"
-        )));
+    let tier_config = load_tier_config(&deps.storage, pool)?;
+
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+
+    // bets on an expired pool go to a new round
+    cancel_expired_round(&mut pool_rounds_store, &tier_config, &env, reveal_timeout)?;
+
+    let mut current_round_state = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+
+    // no-loss rounds take deposits of at least the entry fee instead of bets
+    if let PoolType::NoLoss { .. } = current_round_state.tier_config.pool_type {
+        return try_deposit(deps, env, amount, from, numbers, entropy, current_round_state);
+    }
+
+    if is_round_locked(&current_round_state, env.block.height, reveal_timeout) || !current_round_state.is_betting_open(env.block.time) {
+        return Err(StdError::generic_err("Round is closed for betting!").into());
+    }
+    let round = current_round_state.round_number;
+
+    // bets follow the config the round was opened with, tier changes apply from the next round
+    let tier_config = current_round_state.tier_config.clone();
+
+    // check correct entry fee for the pool selected
+    if tier_config.entry_fee != amount {
+        return Err(StdError::generic_err("Amount invalid of pool choosen").into());
     }
 
-    // check if number is inside the range for that tier
-    let max_rand_number_tier: i16 = load(&tier_config, b"max_rand_number")?;
-    if number < 1 || number > max_rand_number_tier {
+    // check the picked numbers count for the pool type
+    let picks = match &tier_config.pool_type {
+        PoolType::LuckyNumber => 1,
+        PoolType::Lotto { picks, .. } => *picks as usize,
+        PoolType::Raffle | PoolType::NoLoss { .. } => 0
+    };
+    if numbers.len() != picks || (1..numbers.len()).any(|index| numbers[..index].contains(&numbers[index])) {
         return Err(StdError::generic_err(format!(
-            "Number outside valid range for this tier!"
-        )));
+            "Pick {} distinct numbers for this pool!", picks
+        )).into());
+    }
+
+    // check if numbers are inside the range for that pool
+    if numbers.iter().any(|number| *number < 1 || *number > tier_config.max_rand_number) {
+        return Err(StdError::generic_err("Number outside valid range for this pool!").into());
     }
 
-    let tier_rounds_key: String = "tier".to_owned()  + &tier.to_string();
-    let mut tier_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &tier_rounds_key.as_bytes()], &mut deps.storage);
-    let mut tier_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier_rounds)?;
+    let user_round_tickets = load_user_round_bets(&deps.storage, &user_address, pool, round)?.len() as u32;
+    if user_round_tickets >= tier_config.max_tickets_per_user {
+        return Err(StdError::generic_err("User already bought the maximum tickets on this round / pool.").into());
+    }
 
-    let mut current_round_state = tier_rounds_store.get_at(tier_rounds_store.len() - 1).unwrap();
+    // ticket ids are the position on the round tickets, withdrawn tickets keep their id
+    let mut round_tickets = PrefixedStorage::multilevel(&[ROUND_TICKETS, &pool.to_be_bytes(), &round.to_be_bytes()], &mut deps.storage);
+    let mut round_tickets_store: AppendStoreMut<RoundTicket, _> = AppendStoreMut::attach_or_create(&mut round_tickets)?;
+    let ticket = round_tickets_store.len();
+    round_tickets_store.push(&RoundTicket {
+        owner: user_address.clone(),
+        numbers: numbers.clone(),
+        withdrawn: false
+    })?;
 
     // update round state
-    current_round_state.pool_size = current_round_state.pool_size + amount;
-    current_round_state.users_count = current_round_state.users_count + 1;
-    current_round_state.users_picked_numbers_count[number as usize - 1] = current_round_state.users_picked_numbers_count[number as usize - 1] + 1;
-    tier_rounds_store.set_at(tier_rounds_store.len()-1,&current_round_state);
-
-    // how do i know if this user already bet on that tier/round 
-    let mapping_key: String = "tier".to_owned() + &tier.to_string() + "_" + "round" + &current_round_state.round_number.to_string();
-    let mut bets_storage = PrefixedStorage::new(BETS, &mut deps.storage);
-    let user_bets: Result<Option<UserBetsStruct>,StdError> = load(&bets_storage, &user_address.as_slice());
-    
+    current_round_state.pool_size += amount;
+    current_round_state.tickets_count += 1;
+    if user_round_tickets == 0 {
+        current_round_state.users_count += 1;
+    }
+    for number in &numbers {
+        current_round_state.users_picked_numbers_count[*number as usize - 1] += 1;
+    }
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    pool_rounds_store.set_at(round, &current_round_state)?;
+
     //add user bet
-    let user_bet: UserBetStruct = UserBetStruct {
-        round_number: current_round_state.round_number,
-        tier,
-        number,
+    push_user_bet(&mut deps.storage, &user_address, &UserBetStruct {
+        round_number: round,
+        pool,
+        ticket,
+        numbers: numbers.clone(),
         claimed_reward: false,
-        timestamp: env.block.time
-    };
-    let mut user_bets_modified;
-    
-    // { <user_address>: { "bet_keys": [...], "bets": {...} } }
-    if user_bets.is_err() {
-        let mut hashmap: HashMap<String, UserBetStruct> = HashMap::new();
-        hashmap.insert(mapping_key.clone(), user_bet);
-
-        user_bets_modified = UserBetsStruct {
-            bet_keys: vec![ mapping_key.clone() ],
-            bets: hashmap
-        }
-    } else {
-        let user_bets_unwraped = user_bets.unwrap().unwrap();
-        if user_bets_unwraped.bets.contains_key(&mapping_key) {
-            return Err(StdError::generic_err(format!(
-                "User already bet on this round / tier."
-            )));
-       }
-       user_bets_modified = user_bets_unwraped;
-       user_bets_modified.bet_keys.push(mapping_key.clone());
-       user_bets_modified.bets.insert(mapping_key.clone(), user_bet);
+        timestamp: env.block.time,
+        withdrawn: false
+    })?;
+
+    // bettors by picked number, used to find the winners to pay on the draw
+    if tier_config.pool_type == PoolType::LuckyNumber {
+        let mut number_bettors = PrefixedStorage::multilevel(&[ROUND_BETTORS, &pool.to_be_bytes(), &round.to_be_bytes(), &numbers[0].to_be_bytes()], &mut deps.storage);
+        AppendStoreMut::<CanonicalAddr, _>::attach_or_create(&mut number_bettors)?.push(&user_address)?;
     }
 
-    save(&mut bets_storage, &user_address.as_slice(), &Some(user_bets_modified))?;
+    // every bettor adds to the entropy used on the next draws
+    accumulate_entropy(&mut deps.storage, &env, &from, entropy.unwrap_or_default().as_bytes())?;
 
-    // add the bet number to the additional entropy array
-    // As on ChaChaRng only up to 8 words are used, and 2 of them are the base entropy and the entropy sent by the trigger we will save only 6 users entropy on this array
-    let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
-    let mut addition_entropy: Vec<_> = load(&config_data, b"addition_entropy")?;
-    if addition_entropy.len() >= 6 {
-        addition_entropy[0] = number as u64;
-        addition_entropy.rotate_right(1);
-    } else {
-        addition_entropy.push(number as u64)
+    Ok(HandleResponse {
+        messages: vec![],
+        // the picked numbers are only public once the round is drawn
+        log: vec![
+            log("action", "bet"),
+            log("pool", pool),
+            log("round", round),
+            log("ticket", ticket),
+            log("amount", amount)
+        ],
+        data: Some(to_binary(&HandleAnswer::Bet {
+            pool,
+            round,
+            ticket
+        })?),
+    })
+}
+
+/// Deposit on the current round of a no-loss pool, its expiry was already checked by try_bet
+fn try_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+    from: HumanAddr,
+    numbers: Vec<i16>,
+    entropy: Option<String>,
+    mut current_round_state: RoundStruct
+) -> ContractResult<HandleResponse> {
+    let user_address = deps.api.canonical_address(&from)?;
+    let pool = current_round_state.pool;
+    let tier_config = current_round_state.tier_config.clone();
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+
+    if !numbers.is_empty() {
+        return Err(StdError::generic_err("No-loss pools take no numbers!").into());
+    }
+    // dust deposits would hold no ticket
+    if amount < tier_config.entry_fee {
+        return Err(StdError::generic_err("Deposit at least the entry fee of the pool!").into());
+    }
+
+    // deposits after the cutoff would get tickets on the yield of the whole round
+    if is_round_locked(&current_round_state, env.block.height, reveal_timeout) || !current_round_state.is_betting_open(env.block.time) {
+        return Err(StdError::generic_err("Round is closed for deposits!").into());
     }
-    save(&mut config_data, b"addition_entropy", &addition_entropy)?;
 
-    return Ok(HandleResponse {
+    let pool_deposits = ReadonlyPrefixedStorage::multilevel(&[DEPOSITS, &pool.to_be_bytes()], &deps.storage);
+    let balance: Uint128 = may_load(&pool_deposits, user_address.as_slice())?.unwrap_or(Uint128(0));
+    let deposit = balance + amount;
+    save_deposit(&mut deps.storage, pool, &user_address, balance, deposit, &tier_config, &mut current_round_state)?;
+
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    pool_rounds_store.set_at(current_round_state.round_number, &current_round_state)?;
+
+    // depositors add to the entropy like bettors
+    accumulate_entropy(&mut deps.storage, &env, &from, entropy.unwrap_or_default().as_bytes())?;
+
+    Ok(HandleResponse {
         messages: vec![],
-        log: vec![],
-        data: Some(to_binary(&HandleAnswer::Status {
-            status: ResponseStatus::Success,
-            message: None
+        log: vec![
+            log("action", "deposit"),
+            log("pool", pool),
+            log("round", current_round_state.round_number),
+            log("amount", amount)
+        ],
+        data: Some(to_binary(&HandleAnswer::Deposit {
+            pool,
+            deposit
+        })?),
+    })
+}
+
+/// Principal can be withdrawn at any time but while the round is waiting for the lucky number
+pub fn try_withdraw_deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: u32,
+    amount: Uint128
+) -> ContractResult<HandleResponse> {
+    let user_address = deps.api.canonical_address(&env.message.sender)?;
+
+    let tier_config = load_tier_config(&deps.storage, pool)?;
+    if !matches!(tier_config.pool_type, PoolType::NoLoss { .. }) {
+        return Err(StdError::generic_err("Pool does not take deposits!").into());
+    }
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String = load(&config_data, b"token_hash")?;
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    cancel_expired_round(&mut pool_rounds_store, &tier_config, &env, reveal_timeout)?;
+    let mut current_round_state = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+    if is_round_locked(&current_round_state, env.block.height, reveal_timeout) {
+        return Err(StdError::generic_err("Round is closed, waiting for the lucky number!").into());
+    }
+
+    let pool_deposits = ReadonlyPrefixedStorage::multilevel(&[DEPOSITS, &pool.to_be_bytes()], &deps.storage);
+    let balance: Uint128 = may_load(&pool_deposits, user_address.as_slice())?.unwrap_or(Uint128(0));
+    if amount.u128() == 0 || amount > balance {
+        return Err(StdError::generic_err("Withdraw amount must be between 1 and the deposit balance!").into());
+    }
+    let deposit = (balance - amount)?;
+    save_deposit(&mut deps.storage, pool, &user_address, balance, deposit, &tier_config, &mut current_round_state)?;
+
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    pool_rounds_store.set_at(current_round_state.round_number, &current_round_state)?;
+
+    Ok(HandleResponse {
+        messages: vec![transfer_msg(
+            env.message.sender,
+            amount,
+            None,
+            BLOCK_SIZE,
+            token_hash,
+            token_address
+        )?],
+        log: vec![
+            log("action", "withdraw_deposit"),
+            log("pool", pool),
+            log("round", current_round_state.round_number),
+            log("amount", amount)
+        ],
+        data: Some(to_binary(&HandleAnswer::Deposit {
+            pool,
+            deposit
         })?),
     })
 }
@@ -313,25 +478,23 @@ pub fn try_change_admin<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     admin: HumanAddr,
-) -> StdResult<HandleResponse> {
+) -> ContractResult<HandleResponse> {
     let sender = deps.api.canonical_address(&env.message.sender)?;
     let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
     let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
     if sender == owner_address {
         save(&mut config_data, b"owner", &deps.api.canonical_address(&admin)?)?;
-        return Ok(HandleResponse {
+        Ok(HandleResponse {
             messages: vec![],
-            log: vec![],
+            log: vec![log("action", "change_admin")],
             data: Some(to_binary(&HandleAnswer::Status {
                 status: ResponseStatus::Success,
                 message: None
             })?),
         })
     } else {
-        return Err(StdError::generic_err(format!(
-            "User does not permissions to change owner!"
-        )));
+        Err(ContractError::Unauthorized { action: "change owner".to_string() })
     }
 }
 
@@ -339,903 +502,2988 @@ pub fn try_change_triggerer<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     triggerer: HumanAddr,
-) -> StdResult<HandleResponse> {
+) -> ContractResult<HandleResponse> {
     let sender = deps.api.canonical_address(&env.message.sender)?;
     let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
     let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
     if sender == owner_address {
         save(&mut config_data, b"triggerer", &triggerer)?;
-        return Ok(HandleResponse {
+        Ok(HandleResponse {
             messages: vec![],
-            log: vec![],
+            log: vec![log("action", "change_triggerer")],
             data: Some(to_binary(&HandleAnswer::Status {
                 status: ResponseStatus::Success,
                 message: None
             })?),
         })
     } else {
-        return Err(StdError::generic_err(format!(
-            "User does not permissions to change triggerer!"
-        )));
+        Err(ContractError::Unauthorized { action: "change triggerer".to_string() })
     }
     
 }
 
-pub fn try_change_tier<S: Storage, A: Api, Q: Querier>(
+pub fn try_change_treasury<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    tier: i8, 
-    entry_fee: Uint128, 
-    triggerer_fee: Uint128, 
-    min_entries: i16, 
-    max_rand_number: i16
-) -> StdResult<HandleResponse> {
+    treasury: HumanAddr,
+) -> ContractResult<HandleResponse> {
     let sender = deps.api.canonical_address(&env.message.sender)?;
-    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
     let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
-    if sender == owner_address {
-        let tier_config_key = 
-            if tier == 1 { LUCKY_NUMBER_CONFIG_TIER_1 } 
-            else if tier == 2 { LUCKY_NUMBER_CONFIG_TIER_2 } 
-            else if tier == 3 { LUCKY_NUMBER_CONFIG_TIER_3 } 
-            else { 
-                        return Err(StdError::generic_err(format!(
-                            "Tier invalid"
-                        )));
-            };
-            
-        let mut tier_state = PrefixedStorage::new(tier_config_key, &mut deps.storage);
-        save(&mut tier_state, b"entry_fee", &entry_fee)?;
-        save(&mut tier_state, b"triggerer_fee", &triggerer_fee)?;
-        save(&mut tier_state, b"min_entries", &min_entries)?;
-        save(&mut tier_state, b"max_rand_number", &max_rand_number)?;
-
-        return Ok(HandleResponse {
-            messages: vec![],
-            log: vec![],
-            data: Some(to_binary(&HandleAnswer::Status {
-                status: ResponseStatus::Success,
-                message: None
-            })?),
-        })
-    } else {
-        return Err(StdError::generic_err(format!(
-            "User does not permissions to change tiers!"
-        )));
+    if sender != owner_address {
+        return Err(ContractError::Unauthorized { action: "change treasury".to_string() });
     }
+
+    save(&mut config_data, b"treasury", &Some(treasury))?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "change_treasury")],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None
+        })?),
+    })
 }
 
-pub fn try_withdrawl<S: Storage, A: Api, Q: Querier>(
+pub fn try_withdraw_treasury<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    tier: i8,
-    round: u32
-) -> StdResult<HandleResponse> {
-    let user_address = deps.api.canonical_address(&env.message.sender)?;
-
-    // check the tier entry amount
-    let tier_config_key = 
-    if tier == 1 { LUCKY_NUMBER_CONFIG_TIER_1 } 
-        else if tier == 2 { LUCKY_NUMBER_CONFIG_TIER_2 } 
-        else if tier == 3 { LUCKY_NUMBER_CONFIG_TIER_3 } 
-        else { 
-                    return Err(StdError::generic_err(format!(
-                        "Tier invalid"
-                    )));
-        };
-
-    let tier_config = ReadonlyPrefixedStorage::new(tier_config_key, &deps.storage);
-    let entry_fee_tier: Uint128 = load(&tier_config, b"entry_fee")?;
-
-    //get transfer token info
-    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
-    let token_address: HumanAddr = load(&config_data, b"token_address")?;
-    let token_hash: String  = load(&config_data, b"token_hash")?;
-
-    // get that tier/round state
-    let tier_rounds_key: String = "tier".to_owned()  + &tier.to_string();
-    let tier_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &tier_rounds_key.as_bytes()], &deps.storage);
-    let tier_rounds_store: AppendStore<RoundStruct, _> = AppendStore::attach(&tier_rounds).unwrap().unwrap();
-    let round_state = tier_rounds_store.get_at(round).unwrap();
-     
-    // Check if user bet on this tier/round
-    let mapping_key: String = "tier".to_owned() + &tier.to_string() + "_" + "round" + &round.to_string();
-    let mut bets_storage = ReadonlyPrefixedStorage::new(BETS, &deps.storage);
-    let user_bets: Option<UserBetsStruct> = load(&bets_storage, &user_address.as_slice())?;
+) -> ContractResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
+    let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
+    let treasury = match load::<Option<HumanAddr>, _>(&config_data, b"treasury")? {
+        Some(treasury) => treasury,
+        None => return Err(StdError::generic_err("Treasury is not set!").into())
+    };
 
-    if user_bets.clone() == None || !user_bets.clone().unwrap().bets.contains_key(&mapping_key) {
-        return Err(StdError::generic_err(format!(
-            "User does not have any redeemable bet on this tier/round!"
-        )));
+    if sender != owner_address && env.message.sender != treasury {
+        return Err(ContractError::Unauthorized { action: "withdraw the treasury".to_string() });
     }
 
-    // check the user bet state
-    let mut this_user_bets = user_bets.unwrap();
-
-    if this_user_bets.bets.get(&mapping_key).unwrap().claimed_reward {
-        return Err(StdError::generic_err(format!(
-            "This user already claimed the reward for this tier/round!"
-        )));
+    let accrued: Uint128 = load(&config_data, b"treasury_accrued")?;
+    if accrued.u128() == 0 {
+        return Err(StdError::generic_err("Treasury has nothing to withdraw!").into());
     }
+    save(&mut config_data, b"treasury_accrued", &Uint128(0))?;
 
-    let mut transfer_result: CosmosMsg = CosmosMsg::Custom(Empty {});
-
-    // check if round is finished with the lucky number field
-    if round_state.lucky_number == None {
-        // if the round is not finished, the user wants to withdrawl his bet!
-
-        // transfer the tokens
-        transfer_result = transfer_msg(
-            env.message.sender.clone(),
-            entry_fee_tier,
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String = load(&config_data, b"token_hash")?;
+    Ok(HandleResponse {
+        messages: vec![transfer_msg(
+            treasury,
+            accrued,
             None,
             BLOCK_SIZE,
             token_hash,
             token_address
-        ).unwrap();
-
-        // clear round state
-        let mut tier_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &tier_rounds_key.as_bytes()], &mut deps.storage);
-        let mut tier_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier_rounds)?;
-        let mut round_state = tier_rounds_store.get_at(round).unwrap();
-
-        round_state.users_count = round_state.users_count - 1;
-        round_state.pool_size = (round_state.pool_size - entry_fee_tier)?;
-        round_state.users_picked_numbers_count[this_user_bets.bets.get(&mapping_key).unwrap().number as usize - 1] = round_state.users_picked_numbers_count[this_user_bets.bets.get(&mapping_key).unwrap().number as usize - 1] - 1;
-   
-        tier_rounds_store.set_at(round_state.round_number,&round_state);
-
-        // clear user bets
-        let mut bets_storage = PrefixedStorage::new(BETS, &mut deps.storage);
-        if let Some(index) = this_user_bets.bet_keys.iter().position(|value| value == &mapping_key) {
-            this_user_bets.bet_keys.remove(index);
-        } 
-        this_user_bets.bets.remove(&mapping_key.clone());
-        save(&mut bets_storage, &user_address.as_slice(), &Some(this_user_bets))?;
-    } else {
-        // the round is finished so the user wants to redeem the reward
-
-        // check if user is not winner
-        if round_state.lucky_number.unwrap() != this_user_bets.bets.get(&mapping_key).unwrap().number {
-            return Err(StdError::generic_err(format!(
-                "User is not a winner! The bet number is not equal to the lucky number for this tier/round!"
-            )));
-        }
-
-        // winner logic!
-        let win_players_count: u128 = *(round_state.users_picked_numbers_count.get((round_state.lucky_number.unwrap() - 1) as usize)).unwrap() as u128;
-        let amount_for_this_winner = round_state.pool_size.multiply_ratio(Uint128(1), Uint128(win_players_count));
+        )?],
+        log: vec![
+            log("action", "withdraw_treasury"),
+            log("amount", accrued)
+        ],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None
+        })?),
+    })
+}
 
-        transfer_result = transfer_msg(
-            env.message.sender.clone(),
-            amount_for_this_winner,
-            None,
-            BLOCK_SIZE,
-            token_hash,
-            token_address
-        ).unwrap();       
-
-        //
-        // update user bets
-        let mut bets_storage = PrefixedStorage::new(BETS, &mut deps.storage);
-        let current_bet_state =  this_user_bets.bets.get(&mapping_key);
-        if current_bet_state != None {
-            let mut new_bet_state: UserBetStruct = current_bet_state.unwrap().clone();
-            new_bet_state.claimed_reward = true;
-            this_user_bets.bets.insert(mapping_key, new_bet_state.to_owned());
-        } else {
-            return Err(StdError::generic_err(format!(
-                "Cannot Update User Bet state!"
-            )));
-        }
+pub fn try_change_trigger_mode<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    trigger_mode: TriggerMode
+) -> ContractResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
+    let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
-        save(&mut bets_storage, &user_address.as_slice(), &Some(this_user_bets))?;
+    if sender != owner_address {
+        return Err(ContractError::Unauthorized { action: "change the trigger mode".to_string() });
     }
 
+    save(&mut config_data, b"trigger_mode", &trigger_mode)?;
+
     Ok(HandleResponse {
-        messages: vec![
-            transfer_result
-        ],
-        log: vec![],
+        messages: vec![],
+        log: vec![log("action", "change_trigger_mode")],
         data: Some(to_binary(&HandleAnswer::Status {
             status: ResponseStatus::Success,
-            message: None,
+            message: None
         })?),
     })
 }
 
-pub fn try_trigger_lucky_number<S: Storage, A: Api, Q: Querier>(
+pub fn try_create_pool<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    tier1: bool, 
-    tier2: bool,  
-    tier3: bool, 
-    entropy: u64
-) -> StdResult<HandleResponse> {
+    metadata: PoolMetadata,
+    tier_config: TierConfig
+) -> ContractResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
     let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
-    let triggerer_address: HumanAddr = load(&config_data, b"triggerer")?;
-    if triggerer_address != env.message.sender {
-        return Err(StdError::generic_err(format!(
-            "Not the valid triggerer!"
-        )));
+    if sender != owner_address {
+        return Err(ContractError::Unauthorized { action: "create pools".to_string() });
     }
-    let token_address: HumanAddr = load(&config_data, b"token_address")?;
-    let token_hash: String  = load(&config_data, b"token_hash")?;
 
-    // Generate seed vector: original entropy + this request entropy + max 6 entropy stored from users
-    let base_entropy = load(&config_data, b"base_entropy")?;
-    let mut addition_entropy: Vec<_> = load(&config_data, b"addition_entropy")?;
+    validate_tier_config(&tier_config)?;
 
-    addition_entropy.push(base_entropy);
-    addition_entropy.push(entropy.clone().to_be_bytes());
-    
-    let mut hasher = Sha256::new();
-    addition_entropy.iter().for_each(|el| hasher.update(el));
-    let seed:[u8; 32] = hasher.finalize().into();
-    let mut rng = ChaChaRng::from_seed(seed); // ChaChaRng::from_seed Only up to 8 words are used;
-
-    let mut lucky_number_tier_1: i16 = 0;
-    let mut lucky_number_tier_2: i16 = 0;
-    let mut lucky_number_tier_3: i16 = 0;
-    let mut transfer_result: CosmosMsg = CosmosMsg::Custom(Empty {});
-
-    if tier1 == true {
-        let tier1_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_1, &deps.storage);
-        let min_entries_tier1: i16 = load(&tier1_config, b"min_entries")?;
-        let entry_fee_tier1: Uint128 = load(&tier1_config, b"entry_fee")?;
-        let max_rand_number_tier1: i16 = load(&tier1_config, b"max_rand_number")?;
-        let triggerer_fee_tier1: Uint128 = load(&tier1_config, b"triggerer_fee")?;
-
-        let mut tier1_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &mut deps.storage);
-        let mut tier1_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier1_rounds)?;
-        let tier1_cur_round: RoundStruct = tier1_rounds_store.get_at(tier1_rounds_store.len() - 1).unwrap();
-        
-        let pool_size_cur_round_tier1: Uint128 =  tier1_cur_round.pool_size;
-
-        // check if there are enougth pool size (pool_size >= min_entries * entry_fee)
-        if pool_size_cur_round_tier1 >= Uint128(min_entries_tier1 as u128).multiply_ratio(entry_fee_tier1, Uint128(1)) {
-            lucky_number_tier_1 = rng.gen_range(1,max_rand_number_tier1 + 1);
-
-            //update round
-            let mut updated_round = tier1_cur_round;
-            updated_round.lucky_number = Some(lucky_number_tier_1);
-            updated_round.round_end_timestamp = Some(env.block.time);
-            updated_round.pool_size = (updated_round.pool_size - triggerer_fee_tier1)?;
-            updated_round.round_end_pool_size = Some(updated_round.pool_size);
-            let mut next_round_pool_size = Uint128(0);
-            // Check if any winner, if not the pool size will transfer to the next round so this round state will be 0!
-            let win_players_count: u128 = *(updated_round.users_picked_numbers_count.get((lucky_number_tier_1 - 1) as usize)).unwrap() as u128;
-            updated_round.winner_users_count = Some(win_players_count as u32);
-            if win_players_count == 0 {
-                next_round_pool_size = updated_round.pool_size;
-                updated_round.pool_size = Uint128(0);
-            }
-            tier1_rounds_store.set_at(tier1_rounds_store.len()-1,&updated_round);
-
-            //send trigger fee to triggerer
-            transfer_result = transfer_msg(
-                triggerer_address.clone(),
-                triggerer_fee_tier1,
-                None,
-                BLOCK_SIZE,
-                token_hash.clone(),
-                token_address.clone()
-            ).unwrap();
-
-            //new round
-            let new_round: RoundStruct = RoundStruct {
-                round_number: tier1_rounds_store.len(),
-                lucky_number: None,
-                users_count: 0,
-                round_end_timestamp: None,
-                round_end_pool_size: None,
-                pool_size: next_round_pool_size,
-                users_picked_numbers_count: vec![0; (max_rand_number_tier1) as usize],
-                winner_users_count: None
-            };
-            tier1_rounds_store.push(&new_round)?;
-        }
-    }
-
-    if tier2 == true {
-        let tier2_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_2, &deps.storage);
-        let min_entries_tier2: i16 = load(&tier2_config, b"min_entries")?;
-        let entry_fee_tier2: Uint128 = load(&tier2_config, b"entry_fee")?;
-        let max_rand_number_tier2: i16 = load(&tier2_config, b"max_rand_number")?;
-        let triggerer_fee_tier2: Uint128 = load(&tier2_config, b"triggerer_fee")?;
-
-        let mut tier2_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &mut deps.storage);
-        let mut tier2_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier2_rounds)?;
-        let tier2_cur_round: RoundStruct = tier2_rounds_store.get_at(tier2_rounds_store.len() - 1).unwrap();
-        
-        let pool_size_cur_round_tier2: Uint128 =  tier2_cur_round.pool_size;
-
-        // check if there are enougth pool size (pool_size >= min_entries * entry_fee)
-        if pool_size_cur_round_tier2 >= Uint128(min_entries_tier2 as u128).multiply_ratio(entry_fee_tier2, Uint128(1)) {
-            lucky_number_tier_2 = rng.gen_range(1,max_rand_number_tier2 + 1);
-
-            //update round
-            let mut updated_round = tier2_cur_round;
-            updated_round.lucky_number = Some(lucky_number_tier_2);
-            updated_round.round_end_timestamp = Some(env.block.time);
-            updated_round.pool_size = (updated_round.pool_size - triggerer_fee_tier2)?;
-            updated_round.round_end_pool_size = Some(updated_round.pool_size);
-            let mut next_round_pool_size = Uint128(0);
-            // Check if any winner, if not the pool size will transfer to the next round so this round state will be 0!
-            let win_players_count: u128 = *(updated_round.users_picked_numbers_count.get((lucky_number_tier_2 - 1) as usize)).unwrap() as u128;
-            updated_round.winner_users_count = Some(win_players_count as u32);
-            if win_players_count == 0 {
-                next_round_pool_size = updated_round.pool_size;
-                updated_round.pool_size = Uint128(0);
-            }
-            tier2_rounds_store.set_at(tier2_rounds_store.len()-1,&updated_round);
-
-            //send trigger fee to triggerer
-            transfer_result = transfer_msg(
-                triggerer_address.clone(),
-                triggerer_fee_tier2,
-                None,
-                BLOCK_SIZE,
-                token_hash.clone(),
-                token_address.clone()
-            ).unwrap();
-
-            //new round
-            let new_round: RoundStruct = RoundStruct {
-                round_number: tier2_rounds_store.len(),
-                lucky_number: None,
-                users_count: 0,
-                round_end_timestamp: None,
-                round_end_pool_size: None,
-                pool_size: next_round_pool_size,
-                users_picked_numbers_count: vec![0; (max_rand_number_tier2) as usize],
-                winner_users_count: None
-            };
-            tier2_rounds_store.push(&new_round)?;
-        }
-    }
-
-    if tier3 == true {
-        let tier3_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_3, &deps.storage);
-        let min_entries_tier3: i16 = load(&tier3_config, b"min_entries")?;
-        let entry_fee_tier3: Uint128 = load(&tier3_config, b"entry_fee")?;
-        let max_rand_number_tier3: i16 = load(&tier3_config, b"max_rand_number")?;
-        let triggerer_fee_tier3: Uint128 = load(&tier3_config, b"triggerer_fee")?;
-
-        let mut tier3_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &mut deps.storage);
-        let mut tier3_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut tier3_rounds)?;
-        let tier3_cur_round: RoundStruct = tier3_rounds_store.get_at(tier3_rounds_store.len() - 1).unwrap();
-        
-        let pool_size_cur_round_tier3: Uint128 =  tier3_cur_round.pool_size;
-
-        // check if there are enougth pool size (pool_size >= min_entries * entry_fee)
-        if pool_size_cur_round_tier3 >= Uint128(min_entries_tier3 as u128).multiply_ratio(entry_fee_tier3, Uint128(1)) {
-            lucky_number_tier_3 = rng.gen_range(1,max_rand_number_tier3 + 1);
-
-            //update round
-            let mut updated_round = tier3_cur_round;
-            updated_round.lucky_number = Some(lucky_number_tier_3);
-            updated_round.round_end_timestamp = Some(env.block.time);
-            updated_round.pool_size = (updated_round.pool_size - triggerer_fee_tier3)?;
-            updated_round.round_end_pool_size = Some(updated_round.pool_size);
-            let mut next_round_pool_size = Uint128(0);
-            // Check if any winner, if not the pool size will transfer to the next round so this round state will be 0!
-            let win_players_count: u128 = *(updated_round.users_picked_numbers_count.get((lucky_number_tier_3 - 1) as usize)).unwrap() as u128;
-            updated_round.winner_users_count = Some(win_players_count as u32);
-            if win_players_count == 0 {
-                next_round_pool_size = updated_round.pool_size;
-                updated_round.pool_size = Uint128(0);
-            }
-            tier3_rounds_store.set_at(tier3_rounds_store.len()-1,&updated_round);
-
-            //send trigger fee to triggerer
-            transfer_result = transfer_msg(
-                triggerer_address.clone(),
-                triggerer_fee_tier3,
-                None,
-                BLOCK_SIZE,
-                token_hash.clone(),
-                token_address.clone()
-            ).unwrap();
-
-            //new round
-            let new_round: RoundStruct = RoundStruct {
-                round_number: tier3_rounds_store.len(),
-                lucky_number: None,
-                users_count: 0,
-                round_end_timestamp: None,
-                round_end_pool_size: None,
-                pool_size: next_round_pool_size,
-                users_picked_numbers_count: vec![0; (max_rand_number_tier3) as usize],
-                winner_users_count: None
-            };
-            tier3_rounds_store.push(&new_round)?;
-        }
-    }
-
-    return Ok(HandleResponse {
-        messages: vec![
-            transfer_result
-        ],
+    // pool ids are sequential and start at 1
+    let pools_count: u32 = load(&config_data, b"pools_count")?;
+    let pool = pools_count + 1;
+    check_yield_source(&deps.storage, pool, &tier_config)?;
+    save(&mut PrefixedStorage::new(CONFIG_DATA, &mut deps.storage), b"pools_count", &pool)?;
+
+    save_tier_config(&mut deps.storage, pool, &tier_config)?;
+    save_pool_metadata(&mut deps.storage, pool, &metadata)?;
+
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    pool_rounds_store.push(&RoundStruct::new(pool, 0, Uint128(0), &tier_config, env.block.time))?;
+
+    Ok(HandleResponse {
+        messages: yield_source_key_msgs(&deps.storage, &tier_config)?,
         log: vec![
-            //LogAttribute {key: "lucky_number_tier_1".to_string(), value: lucky_number_tier_1.to_string()},
-            //LogAttribute {key: "lucky_number_tier_2".to_string(), value: lucky_number_tier_2.to_string()},
-            //LogAttribute {key: "lucky_number_tier_3".to_string(), value: lucky_number_tier_3.to_string()},
+            log("action", "create_pool"),
+            log("pool", pool)
         ],
-        data: Some(to_binary(&HandleAnswer::Status {
-            status: ResponseStatus::Success,
-            message: None,
+        data: Some(to_binary(&HandleAnswer::CreatePool {
+            pool
         })?),
-    });
-} 
-
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetTriggerer {} => to_binary(&query_triggerer(deps)?),
-        QueryMsg::GetUserBets { user_address, viewing_key, keys} => to_binary(&query_user_bets(deps, user_address, viewing_key, keys)?),
-        QueryMsg::GetPaginatedUserBets { user_address, viewing_key, page, page_size} => to_binary(&query_paginated_user_bets(deps, user_address, viewing_key, page, page_size)?),
-        QueryMsg::GetPaginatedRounds {tier1, tier2, tier3, page, page_size} => to_binary(&query_paginated_rounds(deps,tier1, tier2, tier3, page, page_size)?),
-        QueryMsg::GetRounds {tier1_rounds, tier2_rounds, tier3_rounds} => to_binary(&query_rounds(deps,tier1_rounds, tier2_rounds, tier3_rounds)?),
-        QueryMsg::GetTierConfigs {tier1, tier2, tier3} => to_binary(&query_tier_configs(deps,tier1, tier2, tier3)?),
-        QueryMsg::CheckTriggers{} => to_binary(&query_check_triggers(deps)?),
-    }
+    })
 }
 
-fn query_triggerer<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> QueryResult  {
+pub fn try_change_pool_metadata<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: u32,
+    metadata: PoolMetadata
+) -> ContractResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
     let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
-    let triggerer_address: HumanAddr = load(&config_data, b"triggerer")?;
+    let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
 
-    to_binary(&QueryAnswer::GetTriggerer {
-        triggerer: triggerer_address
-    })
-}
+    if sender != owner_address {
+        return Err(ContractError::Unauthorized { action: "change pools".to_string() });
+    }
 
-fn query_user_bets<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, user_address: HumanAddr, viewing_key: String, keys: Vec<String>) -> QueryResult  {
-    let mut user_bets: Vec<UserBetStruct> = vec![];
-    
-    let user_address_canonical = &deps.api.canonical_address(&user_address)?;
+    // make sure the pool exists before overwriting its metadata
+    load_tier_config(&deps.storage, pool)?;
+    save_pool_metadata(&mut deps.storage, pool, &metadata)?;
 
-    if !is_key_valid(&deps.storage, user_address_canonical, viewing_key)? {
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "change_pool_metadata"),
+            log("pool", pool)
+        ],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None
+        })?),
+    })
+}
+
+/// The new tier config applies from the next round, the current round keeps the config it was opened with
+pub fn try_change_tier<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: u32, 
+    tier_config: TierConfig
+) -> ContractResult<HandleResponse> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let owner_address: CanonicalAddr = load(&config_data, b"owner")?;
+
+    if sender == owner_address {
+        // make sure the pool exists before overwriting its config
+        let current_tier_config = load_tier_config(&deps.storage, pool)?;
+        validate_tier_config(&tier_config)?;
+        check_yield_source(&deps.storage, pool, &tier_config)?;
+
+        // the deposits tickets depend on the no-loss pool entry fee
+        let no_loss = |config: &TierConfig| matches!(config.pool_type, PoolType::NoLoss { .. });
+        if (no_loss(&current_tier_config) || no_loss(&tier_config))
+            && (current_tier_config.pool_type != tier_config.pool_type || current_tier_config.entry_fee != tier_config.entry_fee) {
+            return Err(StdError::generic_err("No-loss pools can't change their pool type or entry fee!").into());
+        }
+
+        save_tier_config(&mut deps.storage, pool, &tier_config)?;
+
+        Ok(HandleResponse {
+            messages: yield_source_key_msgs(&deps.storage, &tier_config)?,
+            log: vec![
+                log("action", "change_tier"),
+                log("pool", pool)
+            ],
+            data: Some(to_binary(&HandleAnswer::Status {
+                status: ResponseStatus::Success,
+                message: None
+            })?),
+        })
+    } else {
+        Err(ContractError::Unauthorized { action: "change tiers".to_string() })
+    }
+}
+
+pub fn try_withdrawl<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: u32,
+    round: u32
+) -> ContractResult<HandleResponse> {
+    let user_address = deps.api.canonical_address(&env.message.sender)?;
+
+    //get transfer token info
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String  = load(&config_data, b"token_hash")?;
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+
+    // get that pool/round state
+    let mut round_state = load_round(&deps.storage, pool, round)?;
+
+    if round_state.cancelled {
+        return Err(StdError::generic_err("Round was cancelled, claim the refund instead!").into());
+    }
+     
+    // Check if user bet on this pool/round
+    let round_bets = load_user_round_bets(&deps.storage, &user_address, pool, round)?;
+    if round_bets.is_empty() {
+        return Err(ContractError::NoBets { pool, round });
+    }
+
+    let amount: Uint128;
+    let tickets: u32;
+
+    if !round_state.is_drawn() {
+        // if the round is not finished, the user wants to withdrawl all his tickets!
+        if is_round_locked(&round_state, env.block.height, reveal_timeout) {
+            return Err(StdError::generic_err("Round is closed, waiting for the lucky number!").into());
+        }
+
+        // clear user bets and round state
+        let mut withdrawn_tickets: Vec<u32> = vec![];
+        for (position, mut bet) in round_bets.clone() {
+            for number in &bet.numbers {
+                round_state.users_picked_numbers_count[*number as usize - 1] -= 1;
+            }
+            withdrawn_tickets.push(bet.ticket);
+            bet.withdrawn = true;
+            save_user_bet(&mut deps.storage, &user_address, position, &bet)?;
+        }
+        clear_user_round_bets(&mut deps.storage, &user_address, pool, round)?;
+
+        let mut round_tickets = PrefixedStorage::multilevel(&[ROUND_TICKETS, &pool.to_be_bytes(), &round.to_be_bytes()], &mut deps.storage);
+        let mut round_tickets_store: AppendStoreMut<RoundTicket, _> = AppendStoreMut::attach_or_create(&mut round_tickets)?;
+        for ticket in withdrawn_tickets {
+            let mut round_ticket = round_tickets_store.get_at(ticket)?;
+            round_ticket.withdrawn = true;
+            round_tickets_store.set_at(ticket, &round_ticket)?;
+        }
+
+        // refunds use the entry fee the tickets were bought with
+        amount = Uint128(round_state.tier_config.entry_fee.u128() * round_bets.len() as u128);
+        tickets = round_bets.len() as u32;
+        round_state.users_count -= 1;
+        round_state.tickets_count -= round_bets.len() as u32;
+        round_state.pool_size = (round_state.pool_size - amount)?;
+
+        let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+        let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+        pool_rounds_store.set_at(round, &round_state)?;
+    } else {
+        // the round is finished so the user wants to redeem the reward of his winning tickets
+        let mut prizes: u128 = 0;
+        let mut winning_tickets: u32 = 0;
+        let mut claimed_tickets: u32 = 0;
+        for (position, mut bet) in round_bets {
+            let prize = round_state.ticket_prize(bet.ticket, &bet.numbers);
+            if prize.u128() == 0 {
+                continue;
+            }
+            winning_tickets += 1;
+            if !bet.claimed_reward {
+                bet.claimed_reward = true;
+                prizes += prize.u128();
+                claimed_tickets += 1;
+                save_user_bet(&mut deps.storage, &user_address, position, &bet)?;
+            }
+        }
+
+        if winning_tickets == 0 {
+            return Err(ContractError::NotAWinner { pool, round });
+        }
+        if prizes == 0 {
+            return Err(ContractError::AlreadyClaimed { pool, round });
+        }
+        amount = Uint128(prizes);
+        tickets = claimed_tickets;
+    }
+
+    Ok(HandleResponse {
+        messages: vec![transfer_msg(
+            env.message.sender,
+            amount,
+            None,
+            BLOCK_SIZE,
+            token_hash,
+            token_address
+        )?],
+        // refunds and prizes log the same attributes
+        log: vec![
+            log("action", "withdrawl"),
+            log("pool", pool),
+            log("round", round),
+            log("tickets", tickets),
+            log("amount", amount)
+        ],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None,
+        })?),
+    })
+}
+
+pub fn try_claim_refund<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pool: u32,
+    round: u32
+) -> ContractResult<HandleResponse> {
+    let user_address = deps.api.canonical_address(&env.message.sender)?;
+    let tier_config = load_tier_config(&deps.storage, pool)?;
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String  = load(&config_data, b"token_hash")?;
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+
+    // the current round is cancelled by the first refund claimed after it expired
+    let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+    let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+    cancel_expired_round(&mut pool_rounds_store, &tier_config, &env, reveal_timeout)?;
+    let round_state = if round < pool_rounds_store.len() { Some(pool_rounds_store.get_at(round)?) } else { None };
+    let entry_fee = match round_state {
+        Some(round_state) if round_state.cancelled => round_state.tier_config.entry_fee,
+        _ => return Err(StdError::generic_err(format!(
+            "Round {} of pool {} was not cancelled!", round, pool
+        )).into())
+    };
+
+    let round_bets = load_user_round_bets(&deps.storage, &user_address, pool, round)?;
+    if round_bets.is_empty() {
+        return Err(ContractError::NoBets { pool, round });
+    }
+    let mut refunded_tickets: u128 = 0;
+    for (position, mut bet) in round_bets {
+        if !bet.claimed_reward {
+            bet.claimed_reward = true;
+            refunded_tickets += 1;
+            save_user_bet(&mut deps.storage, &user_address, position, &bet)?;
+        }
+    }
+    if refunded_tickets == 0 {
+        return Err(ContractError::AlreadyClaimed { pool, round });
+    }
+    let refund = Uint128(entry_fee.u128() * refunded_tickets);
+
+    Ok(HandleResponse {
+        messages: vec![transfer_msg(
+            env.message.sender,
+            refund,
+            None,
+            BLOCK_SIZE,
+            token_hash,
+            token_address
+        )?],
+        log: vec![
+            log("action", "refund"),
+            log("pool", pool),
+            log("round", round),
+            log("tickets", refunded_tickets),
+            log("amount", refund)
+        ],
+        data: Some(to_binary(&HandleAnswer::Status {
+            status: ResponseStatus::Success,
+            message: None,
+        })?),
+    })
+}
+
+pub fn try_claim_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    start: Option<u32>,
+    limit: Option<u32>
+) -> ContractResult<HandleResponse> {
+    let user_address = deps.api.canonical_address(&env.message.sender)?;
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String  = load(&config_data, b"token_hash")?;
+
+    let user_bets = ReadonlyPrefixedStorage::multilevel(&[USER_BETS, user_address.as_slice()], &deps.storage);
+    let user_bets_store = match AppendStore::<UserBetStruct, _>::attach(&user_bets) {
+        Some(store) => store?,
+        None => return Err(StdError::generic_err("User does not have any bet!").into())
+    };
+
+    // long histories are claimed in several transactions, pages past the last bet are empty
+    let start = start.unwrap_or(0);
+    if start >= user_bets_store.len() {
+        return Ok(HandleResponse {
+            messages: vec![],
+            log: vec![
+                log("action", "claim_all"),
+                log("claimed_bets", 0),
+                log("amount", Uint128(0))
+            ],
+            data: Some(to_binary(&HandleAnswer::ClaimAll {
+                claimed_bets: 0,
+                amount: Uint128(0),
+                next_start: None
+            })?),
+        });
+    }
+    let end = user_bets_store.len().min(start.saturating_add(limit.unwrap_or(MAX_CLAIM_ALL_BETS).min(MAX_CLAIM_ALL_BETS)));
+    let mut claimed: Vec<(u32, UserBetStruct)> = vec![];
+    let mut amount = Uint128(0);
+
+    for position in start..end {
+        let mut bet = user_bets_store.get_at(position)?;
+        if bet.claimed_reward || bet.withdrawn {
+            continue;
+        }
+
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &bet.pool.to_be_bytes()], &deps.storage);
+        let round_state: RoundStruct = match AppendStore::<RoundStruct, _>::attach(&pool_rounds) {
+            Some(store) => store?.get_at(bet.round_number)?,
+            None => continue
+        };
+        let prize = round_state.ticket_prize(bet.ticket, &bet.numbers);
+        if prize.u128() == 0 {
+            continue;
+        }
+
+        amount += prize;
+        bet.claimed_reward = true;
+        claimed.push((position, bet));
+    }
+
+    let next_start = if end < user_bets_store.len() { Some(end) } else { None };
+    let claimed_bets = claimed.len() as u32;
+    for (position, bet) in claimed {
+        save_user_bet(&mut deps.storage, &user_address, position, &bet)?;
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if amount.u128() > 0 {
+        messages.push(transfer_msg(
+            env.message.sender,
+            amount,
+            None,
+            BLOCK_SIZE,
+            token_hash,
+            token_address
+        )?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "claim_all"),
+            log("claimed_bets", claimed_bets),
+            log("amount", amount)
+        ],
+        data: Some(to_binary(&HandleAnswer::ClaimAll {
+            claimed_bets,
+            amount,
+            next_start
+        })?),
+    })
+}
+
+pub fn try_commit_entropy<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pools: Vec<u32>,
+    entropy_commitment: Binary
+) -> ContractResult<HandleResponse> {
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+
+    check_trigger_permission(&config_data, &env.message.sender)?;
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+    let triggerer_address: HumanAddr = load(&config_data, b"triggerer")?;
+
+    // Snapshot the seed: entropy accumulated from every user + original entropy, taken now so nothing done
+    // between the commitment and the reveal changes the draw
+    let base_entropy: [u8; 8] = load(&config_data, b"base_entropy")?;
+    let entropy_accumulator: [u8; 32] = load(&config_data, b"entropy_accumulator")?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(entropy_accumulator);
+    hasher.update(base_entropy);
+    let seed_hash: [u8; 32] = hasher.finalize().into();
+
+    if entropy_commitment.len() != 32 {
+        return Err(StdError::generic_err("Entropy commitment must be a sha256 hash!").into());
+    }
+
+    let mut committed_pools: Vec<u32> = vec![];
+    let mut cancelled_pools: Vec<u32> = vec![];
+
+    for pool in pools {
+        let tier_config = load_tier_config(&deps.storage, pool)?;
+
+        let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+        let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+        if cancel_expired_round(&mut pool_rounds_store, &tier_config, &env, reveal_timeout)? {
+            cancelled_pools.push(pool);
+            continue;
+        }
+        let mut cur_round: RoundStruct = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+
+        if is_round_locked(&cur_round, env.block.height, reveal_timeout) {
+            // the first commitment of a round wins, other triggerers just skip the pool
+            if cur_round.committer.as_ref() != Some(&env.message.sender) {
+                continue;
+            }
+            return Err(StdError::generic_err(format!(
+                "Pool {} is already waiting for the lucky number reveal!", pool
+            )).into());
+        }
+
+        // only rounds past their close time with min entries can be drawn
+        if !cur_round.is_closed(env.block.time) || !cur_round.has_min_entries() {
+            continue;
+        }
+
+        // an expired commitment was never revealed, committing and walking away would block the draws for free
+        // so from then on the round only takes commitments from the triggerer
+        if let Some(committer) = cur_round.committer.take() {
+            cur_round.missed_reveals.push(committer);
+        }
+        if !cur_round.missed_reveals.is_empty() && env.message.sender != triggerer_address {
+            continue;
+        }
+
+        // closes the round for betting until the entropy is revealed or the commitment expires
+        cur_round.entropy_commitment = Some(entropy_commitment.clone());
+        cur_round.commitment_height = Some(env.block.height);
+        cur_round.committer = Some(env.message.sender.clone());
+        cur_round.seed_hash = Some(Binary(seed_hash.to_vec()));
+        pool_rounds_store.set_at(pool_rounds_store.len() - 1, &cur_round)?;
+
+        committed_pools.push(pool);
+    }
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "commit_entropy"),
+            log("pools", join_pools(&committed_pools)),
+            log("cancelled", join_pools(&cancelled_pools))
+        ],
+        data: Some(to_binary(&HandleAnswer::CommitEntropy {
+            pools: committed_pools,
+            cancelled: cancelled_pools
+        })?),
+    })
+}
+
+pub fn try_trigger_lucky_number<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pools: Vec<u32>,
+    entropy: String
+) -> ContractResult<HandleResponse> {
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+
+    check_trigger_permission(&config_data, &env.message.sender)?;
+    let token_address: HumanAddr = load(&config_data, b"token_address")?;
+    let token_hash: String  = load(&config_data, b"token_hash")?;
+    let reveal_timeout: u64 = load(&config_data, b"reveal_timeout")?;
+    let token_vk: String = load(&config_data, b"token_vk")?;
+
+    let revealed_commitment = Binary(sha_256(entropy.as_bytes()).to_vec());
+
+    let mut transfers: Vec<(HumanAddr, Uint128)> = vec![];
+    let mut prizes: Vec<(HumanAddr, Uint128)> = vec![];
+    // house fees and jackpot overflows of the drawn pools, accrued on the treasury
+    let mut treasury_fees = Uint128(0);
+    // no-loss pools fees and prizes are paid by their yield source
+    let mut yield_messages: Vec<CosmosMsg> = vec![];
+    let mut summaries: Vec<PoolDrawSummary> = vec![];
+    let mut logs: Vec<LogAttribute> = vec![log("action", "trigger_lucky_number")];
+
+    for pool in pools {
+        // the next round opens with the latest tier config
+        let next_tier_config = load_tier_config(&deps.storage, pool)?;
+
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &deps.storage);
+        let pool_rounds_store: AppendStore<RoundStruct, _> = match AppendStore::attach(&pool_rounds) {
+            Some(result) => result?,
+            None => return Err(ContractError::InvalidPool { pool })
+        };
+        let cur_round: RoundStruct = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+        let tier_config = cur_round.tier_config.clone();
+
+        // only rounds with a commitment can be revealed, in a later block and before it expires
+        let commitment_height = match cur_round.commitment_height {
+            Some(commitment_height) => commitment_height,
+            None => continue
+        };
+        // only the committer knows the entropy, rounds committed by someone else are skipped
+        if cur_round.committer.as_ref() != Some(&env.message.sender) {
+            continue;
+        }
+        if env.block.height <= commitment_height {
+            return Err(StdError::generic_err(format!(
+                "Entropy for pool {} can only be revealed after the commitment block!", pool
+            )).into());
+        }
+        if env.block.height > commitment_height + reveal_timeout {
+            return Err(StdError::generic_err(format!(
+                "Entropy commitment for pool {} expired!", pool
+            )).into());
+        }
+        if cur_round.entropy_commitment != Some(revealed_commitment.clone()) {
+            return Err(StdError::generic_err(format!(
+                "Revealed entropy does not match the commitment for pool {}!", pool
+            )).into());
+        }
+
+        if !cur_round.has_min_entries() {
+            continue;
+        }
+
+        // every pool round gets its own seed from the commitment snapshot and this request revealed entropy
+        // so it can be verified on its own later
+        let seed_hash = match &cur_round.seed_hash {
+            Some(seed_hash) => seed_hash.clone(),
+            None => return Err(StdError::generic_err(format!(
+                "Entropy commitment for pool {} has no seed!", pool
+            )).into())
+        };
+        let seed = draw_seed(seed_hash.as_slice(), entropy.as_bytes(), pool, cur_round.round_number);
+        let max_number = cur_round.users_picked_numbers_count.len() as i16;
+        let lucky_numbers: Vec<i16> = match &tier_config.pool_type {
+            PoolType::LuckyNumber => draw_lucky_numbers(seed, max_number, 1),
+            PoolType::Lotto { picks, .. } => draw_lucky_numbers(seed, max_number, *picks),
+            PoolType::Raffle | PoolType::NoLoss { .. } => vec![]
+        };
+
+        //update round
+        let mut updated_round = cur_round;
+        updated_round.lucky_number = lucky_numbers.first().copied();
+        updated_round.lucky_numbers = lucky_numbers.clone();
+        updated_round.revealed_entropy = Some(entropy.clone());
+        updated_round.draw_version = Some(DRAW_VERSION);
+        updated_round.round_end_timestamp = Some(env.block.time);
+
+        // no-loss prizes are the yield accrued on the yield source, the deposits are never paid out as prizes
+        let prize_pool_size = match &tier_config.pool_type {
+            PoolType::NoLoss { yield_source, yield_source_hash } => {
+                balance_query(&deps.querier, env.contract.address.clone(), token_vk.clone(), BLOCK_SIZE, yield_source_hash.clone(), yield_source.clone())?.amount
+            }
+            _ => updated_round.pool_size
+        };
+        let triggerer_fee = draw_triggerer_fee(&tier_config, prize_pool_size);
+        updated_round.entries_pool_size = (updated_round.pool_size - updated_round.carried_pool_size)?;
+        updated_round.pool_size = (prize_pool_size - triggerer_fee)?;
+        updated_round.house_fee = updated_round.pool_size.multiply_ratio(tier_config.house_fee_bps as u128, 10_000u128);
+        updated_round.pool_size = (updated_round.pool_size - updated_round.house_fee)?;
+        treasury_fees += updated_round.house_fee;
+        updated_round.round_end_pool_size = Some(updated_round.pool_size);
+
+        let win_players_count: u128;
+        let mut next_round_pool_size: Uint128;
+        let mut ticket_winners: TicketWinners = vec![];
+        match &tier_config.pool_type {
+            PoolType::LuckyNumber => {
+                // Check if any winner, if not the pool size will transfer to the next round so this round state will be 0!
+                win_players_count = updated_round.users_picked_numbers_count[(lucky_numbers[0] - 1) as usize] as u128;
+                if win_players_count == 0 {
+                    next_round_pool_size = updated_round.pool_size;
+                    updated_round.pool_size = Uint128(0);
+                } else {
+                    next_round_pool_size = Uint128(0);
+                }
+            }
+            PoolType::Lotto { brackets, .. } => {
+                // unwon brackets and the prize division rest move to the next round
+                let (results, winners) = draw_lotto_brackets(&deps.storage, pool, updated_round.round_number, &lucky_numbers, brackets, updated_round.pool_size)?;
+                let distributed: u128 = results.iter().map(|result| result.prize_per_ticket.u128() * result.winners as u128).sum();
+                win_players_count = winners.len() as u128;
+                next_round_pool_size = (updated_round.pool_size - Uint128(distributed))?;
+                updated_round.pool_size = Uint128(distributed);
+                updated_round.brackets = results;
+                ticket_winners = winners;
+            }
+            PoolType::Raffle => {
+                match draw_raffle_ticket(&deps.storage, pool, updated_round.round_number, seed, updated_round.tickets_count)? {
+                    Some((owner, ticket)) => {
+                        win_players_count = 1;
+                        next_round_pool_size = Uint128(0);
+                        updated_round.winning_ticket = Some(ticket);
+                        ticket_winners.push((owner, ticket, updated_round.pool_size));
+                    }
+                    None => {
+                        win_players_count = 0;
+                        next_round_pool_size = updated_round.pool_size;
+                        updated_round.pool_size = Uint128(0);
+                    }
+                }
+            }
+            PoolType::NoLoss { yield_source, yield_source_hash } => {
+                // without winner the yield stays on the yield source for the next rounds
+                next_round_pool_size = Uint128(0);
+                let winner = match updated_round.tickets_count {
+                    0 => None,
+                    tickets_count => {
+                        let position = draw_ticket(seed, tickets_count);
+                        deposit_ticket_owner(&deps.storage, pool, tier_config.entry_fee, position)?.map(|owner| (owner, position))
+                    }
+                };
+                match winner {
+                    Some((owner, position)) => {
+                        let winner = deps.api.human_address(&owner)?;
+                        win_players_count = 1;
+                        updated_round.winning_ticket = Some(position);
+                        updated_round.winner = Some(winner.clone());
+                        if updated_round.pool_size.u128() > 0 {
+                            yield_messages.push(transfer_msg(winner, updated_round.pool_size, None, BLOCK_SIZE, yield_source_hash.clone(), yield_source.clone())?);
+                        }
+                    }
+                    None => {
+                        win_players_count = 0;
+                        updated_round.pool_size = Uint128(0);
+                    }
+                }
+            }
+        }
+        updated_round.winner_tickets_count = Some(win_players_count as u32);
+
+        // the carry over is capped by the pool jackpot, the overflow goes to the treasury or to the tickets that did not win
+        if let Some(jackpot) = &tier_config.jackpot {
+            if next_round_pool_size > jackpot.max_carry_over {
+                let overflow = (next_round_pool_size - jackpot.max_carry_over)?;
+                match &jackpot.overflow {
+                    JackpotOverflow::Treasury => {
+                        treasury_fees += overflow;
+                        updated_round.overflow_pool_size = overflow;
+                        next_round_pool_size = jackpot.max_carry_over;
+                    }
+                    JackpotOverflow::SecondaryPrize => {
+                        // without losing tickets or for the split rest the overflow is still carried over
+                        let losing_tickets = updated_round.tickets_count as u128 - win_players_count;
+                        if losing_tickets > 0 {
+                            let prize_per_ticket = overflow.multiply_ratio(1u128, losing_tickets);
+                            updated_round.secondary_prize_per_ticket = prize_per_ticket;
+                            updated_round.overflow_pool_size = Uint128(prize_per_ticket.u128() * losing_tickets);
+                            next_round_pool_size = (next_round_pool_size - updated_round.overflow_pool_size)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &mut deps.storage);
+        let mut pool_rounds_store: AppendStoreMut<RoundStruct, _> = AppendStoreMut::attach_or_create(&mut pool_rounds)?;
+        pool_rounds_store.set_at(pool_rounds_store.len()-1,&updated_round)?;
+
+        //send trigger fee to the triggerer of this round
+        match &tier_config.pool_type {
+            PoolType::NoLoss { yield_source, yield_source_hash } => if triggerer_fee.u128() > 0 {
+                yield_messages.push(transfer_msg(env.message.sender.clone(), triggerer_fee, None, BLOCK_SIZE, yield_source_hash.clone(), yield_source.clone())?);
+            },
+            _ => transfers.push((env.message.sender.clone(), triggerer_fee))
+        }
+
+        //new round
+        pool_rounds_store.push(&updated_round.next(next_round_pool_size, &next_tier_config, env.block.time))?;
+
+        // pay the winners now when the pool opted in and there are few enough of them for the gas limit,
+        // no-loss winners are always paid on the draw
+        let prizes_distributed = match tier_config.pool_type {
+            PoolType::NoLoss { .. } => win_players_count > 0,
+            _ => tier_config.auto_distribute && win_players_count > 0 && win_players_count <= tier_config.max_auto_winners as u128
+        };
+        if prizes_distributed {
+            match &tier_config.pool_type {
+                PoolType::LuckyNumber => {
+                    let prize_per_ticket = updated_round.ticket_prize(0, &lucky_numbers);
+                    for (winner, winning_tickets) in claim_winning_bets(&mut deps.storage, &deps.api, pool, updated_round.round_number, lucky_numbers[0])? {
+                        prizes.push((winner, Uint128(prize_per_ticket.u128() * winning_tickets)));
+                    }
+                }
+                PoolType::Lotto { .. } | PoolType::Raffle => {
+                    prizes.extend(claim_tickets(&mut deps.storage, &deps.api, pool, updated_round.round_number, ticket_winners)?);
+                }
+                PoolType::NoLoss { .. } => {}
+            }
+        }
+
+        // every drawn pool logs the same attributes, in the order of the pools
+        let lucky_numbers_log: Vec<String> = lucky_numbers.iter().map(|number| number.to_string()).collect();
+        logs.push(log("pool", pool));
+        logs.push(log("round", updated_round.round_number));
+        logs.push(log("winner_tickets", win_players_count));
+        logs.push(log("prize_pool_size", updated_round.pool_size));
+        logs.push(log("lucky_number", if lucky_numbers_log.is_empty() { "none".to_string() } else { lucky_numbers_log.join(",") }));
+        logs.push(log("winning_ticket", updated_round.winning_ticket.map_or("none".to_string(), |ticket| ticket.to_string())));
+        logs.push(log("triggerer_fee", triggerer_fee));
+        logs.push(log("house_fee", updated_round.house_fee));
+        summaries.push(PoolDrawSummary {
+            pool,
+            round: updated_round.round_number,
+            lucky_numbers,
+            winning_ticket: updated_round.winning_ticket,
+            winner_tickets_count: win_players_count as u32,
+            prize_pool_size: updated_round.pool_size,
+            triggerer_fee,
+            house_fee: updated_round.house_fee,
+            next_round_pool_size,
+            overflow_pool_size: updated_round.overflow_pool_size,
+            prizes_distributed
+        });
+    }
+
+    // house fees and jackpot overflows accrue until the treasury withdraws them
+    if treasury_fees.u128() > 0 {
+        let mut config_data = PrefixedStorage::new(CONFIG_DATA, &mut deps.storage);
+        let treasury_accrued: Uint128 = load(&config_data, b"treasury_accrued")?;
+        let treasury_collected: Uint128 = load(&config_data, b"treasury_collected")?;
+        save(&mut config_data, b"treasury_accrued", &(treasury_accrued + treasury_fees))?;
+        save(&mut config_data, b"treasury_collected", &(treasury_collected + treasury_fees))?;
+    }
+
+    let mut messages = aggregated_transfer_msgs(transfers, &token_hash, &token_address)?;
+    if !prizes.is_empty() {
+        messages.push(Snip20Msg::batch_transfer(aggregate_transfers(prizes)).to_cosmos_msg(BLOCK_SIZE, token_hash.clone(), token_address.clone())?);
+    }
+    messages.extend(yield_messages);
+
+    Ok(HandleResponse {
+        messages,
+        log: logs,
+        data: Some(to_binary(&HandleAnswer::TriggerLuckyNumber {
+            pools: summaries
+        })?),
+    })
+} 
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let response = match msg {
+        QueryMsg::GetTriggerer {} => to_binary(&query_triggerer(deps)?),
+        QueryMsg::GetUserBets { user_address, viewing_key, keys} => to_binary(&query_user_bets(deps, &viewing_key_owner(deps, &user_address, viewing_key)?, keys)?),
+        QueryMsg::GetPaginatedUserBets { user_address, viewing_key, page, page_size} => to_binary(&query_paginated_user_bets(deps, &viewing_key_owner(deps, &user_address, viewing_key)?, page, page_size)?),
+        QueryMsg::GetPaginatedRounds {pools, page, page_size} => to_binary(&query_paginated_rounds(deps, pools, page, page_size)?),
+        QueryMsg::GetRounds {rounds} => to_binary(&query_rounds(deps, rounds)?),
+        QueryMsg::GetTierConfigs {pools} => to_binary(&query_tier_configs(deps, pools)?),
+        QueryMsg::GetPools {} => to_binary(&query_pools(deps)?),
+        QueryMsg::CheckTriggers {} => to_binary(&query_check_triggers(deps)?),
+        QueryMsg::VerifyRound { pool, round } => to_binary(&query_verify_round(deps, pool, round)?),
+        QueryMsg::GetDeposit { pool, user_address, viewing_key } => to_binary(&query_deposit(deps, pool, &viewing_key_owner(deps, &user_address, viewing_key)?)?),
+        QueryMsg::GetTreasury {} => to_binary(&query_treasury(deps)?),
+        QueryMsg::WithPermit { permit, query } => to_binary(&query_with_permit(deps, permit, query)?),
+    };
+    pad_query_result(response, BLOCK_SIZE)
+}
+
+fn query_with_permit<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, permit: Permit, query: QueryWithPermit) -> ContractResult<Binary> {
+    let owner = permit_owner(deps, &permit)?;
+
+    match query {
+        QueryWithPermit::GetUserBets { keys } => query_user_bets(deps, &owner, keys),
+        QueryWithPermit::GetPaginatedUserBets { page, page_size } => query_paginated_user_bets(deps, &owner, page, page_size),
+        QueryWithPermit::GetDeposit { pool } => query_deposit(deps, pool, &owner),
+    }
+}
+
+/// The user of a viewing key query, once the key is checked
+fn viewing_key_owner<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, user_address: &HumanAddr, viewing_key: String) -> ContractResult<CanonicalAddr> {
+    let user_address_canonical = deps.api.canonical_address(user_address)?;
+    if !is_key_valid(&deps.storage, &user_address_canonical, viewing_key)? {
+        return Err(ContractError::InvalidViewingKey);
+    }
+    Ok(user_address_canonical)
+}
+
+/// The signer of a permit for this contract and chain that wasn't revoked and grants the owner permission
+fn permit_owner<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, permit: &Permit) -> ContractResult<CanonicalAddr> {
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let contract_address: HumanAddr = load(&config_data, b"contract_address")?;
+    let chain_id: String = load(&config_data, b"chain_id")?;
+
+    if !permit.params.allowed_tokens.contains(&contract_address) {
+        return Err(ContractError::InvalidPermit { reason: "permit is not for this contract".to_string() });
+    }
+    if permit.params.chain_id != chain_id {
+        return Err(ContractError::InvalidPermit { reason: "permit is not for this chain".to_string() });
+    }
+    let user_address_canonical = deps.api.canonical_address(&permit.signer()?)?;
+
+    let revoked_permits = ReadonlyPrefixedStorage::multilevel(&[PREFIX_REVOKED_PERMITS, user_address_canonical.as_slice()], &deps.storage);
+    if may_load::<bool, _>(&revoked_permits, permit.params.permit_name.as_bytes())?.is_some() {
+        return Err(ContractError::InvalidPermit { reason: format!("permit {} was revoked", permit.params.permit_name) });
+    }
+    if !permit.check_permission(&Permission::Owner) {
+        return Err(ContractError::MissingPermitPermission);
+    }
+    Ok(user_address_canonical)
+}
+
+fn query_triggerer<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> ContractResult<Binary>  {
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let triggerer_address: HumanAddr = load(&config_data, b"triggerer")?;
+    let trigger_mode: TriggerMode = load(&config_data, b"trigger_mode")?;
+
+    Ok(to_binary(&QueryAnswer::GetTriggerer {
+        triggerer: triggerer_address,
+        trigger_mode
+    })?)
+}
+
+fn query_user_bets<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, user_address_canonical: &CanonicalAddr, keys: Vec<String>) -> ContractResult<Binary>  {
+    let mut user_bets: Vec<UserBetStruct> = vec![];
+
+    for key in keys {
+        if let Some((_, bet_state)) = load_user_bet(&deps.storage, user_address_canonical, &key)? {
+            user_bets.push(bet_state);
+        }
+    }
+
+    Ok(to_binary(&QueryAnswer::GetUserBets {
+        user_bets
+    })?)
+}
+
+fn query_paginated_user_bets<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, 
+    user_address_canonical: &CanonicalAddr,
+    page: u32,
+    page_size: u32
+) -> ContractResult<Binary>  {
+    let mut user_bets : Vec<UserBetStruct> = vec![];
+    let mut bet_rounds: Vec<RoundStruct> = vec![];
+    let mut user_bets_total_count = 0;
+
+    let user_bets_storage = ReadonlyPrefixedStorage::multilevel(&[USER_BETS, user_address_canonical.as_slice()], &deps.storage);
+    let page_bets: Vec<UserBetStruct> = match AppendStore::<UserBetStruct, _>::attach(&user_bets_storage) {
+        Some(result) => {
+            let user_bets_store = result?;
+            user_bets_total_count = user_bets_store.len() as usize;
+            user_bets_store
+            .iter()
+            .rev()
+            .skip(page.saturating_mul(page_size) as _)
+            .take(page_size as _)
+            .collect::<StdResult<Vec<UserBetStruct>>>()?
+        }
+        None => vec![]
+    };
+
+    for bet_state in page_bets {
+        bet_rounds.push(load_round(&deps.storage, bet_state.pool, bet_state.round_number)?.public());
+        user_bets.push(bet_state);
+    }
+  
+    Ok(to_binary(&QueryAnswer::GetPaginatedUserBets {
+        user_bets,
+        bet_rounds,
+        user_bets_total_count
+    })?)
+}
+
+
+fn query_paginated_rounds<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pools: Vec<u32>,
+    page: u32,
+    page_size: u32,
+) -> ContractResult<Binary> {
+    let mut rounds: Vec<RoundStruct> = vec![];
+
+    for pool in pools {
+        let pool_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &deps.storage);
+        let pool_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&pool_rounds_storage) {
+            result?
+        } else {
+            continue;
+        };
+
+        let rounds_iter = pool_rounds_store
+        .iter()
+        .rev()
+        .skip(page.saturating_mul(page_size) as _)
+        .take(page_size as _)
+        .map(|x| Ok(x?.public()));
+        
+        let pool_page: StdResult<Vec<RoundStruct>> = rounds_iter.collect();
+        rounds.extend(pool_page?);
+    }
+    
+    Ok(to_binary(&QueryAnswer::GetPaginatedRounds {
+        rounds
+    })?)
+}
+
+fn query_rounds <S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool_rounds: Vec<PoolRound>,
+) -> ContractResult<Binary> {
+    let mut rounds : Vec<RoundStruct> = vec![];
+
+    for pool_round in pool_rounds {
+        rounds.push(load_round(&deps.storage, pool_round.pool, pool_round.round)?.public())
+    }
+    
+    Ok(to_binary(&QueryAnswer::GetRounds {
+        rounds
+    })?)
+}
+
+fn query_tier_configs<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pools: Vec<u32>
+) -> ContractResult<Binary> {
+    let mut tier_configs: Vec<PoolTierConfig> = vec![];
+
+    for pool in pools {
+        tier_configs.push(PoolTierConfig {
+            pool,
+            config: load_tier_config(&deps.storage, pool)?
+        });
+    }
+    
+    Ok(to_binary(&QueryAnswer::GetTierConfigs {
+        tier_configs
+    })?)
+}
+
+fn query_pools<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> ContractResult<Binary> {
+    let mut pools: Vec<PoolInfo> = vec![];
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let pools_count: u32 = load(&config_data, b"pools_count")?;
+
+    for pool in 1..=pools_count {
+        let metadata = load_pool_metadata(&deps.storage, pool)?;
+        pools.push(PoolInfo {
+            pool,
+            name: metadata.name,
+            description: metadata.description,
+            display_order: metadata.display_order,
+            config: load_tier_config(&deps.storage, pool)?
+        });
+    }
+    pools.sort_by_key(|pool_info| (pool_info.display_order, pool_info.pool));
+
+    Ok(to_binary(&QueryAnswer::GetPools {
+        pools
+    })?)
+}
+
+fn query_check_triggers<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>
+) -> ContractResult<Binary> {
+    let mut pools: Vec<PoolTrigger> = vec![];
+    let mut pending_reveal: Vec<u32> = vec![];
+    let mut expiring: Vec<PoolTrigger> = vec![];
+
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let pools_count: u32 = load(&config_data, b"pools_count")?;
+
+    for pool in 1..=pools_count {
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &deps.storage);
+        let pool_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&pool_rounds) {
+            result?
+        } else {
+            continue;
+        };
+            
+        let cur_round: RoundStruct = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+
+        if cur_round.commitment_height.is_some() {
+            // queries have no block height, so expired commitments are reported here too and can be committed again
+            pending_reveal.push(pool);
+        } else if cur_round.tickets_count >= cur_round.tier_config.min_entries as u32 {
+            pools.push(PoolTrigger { pool, time: cur_round.closes_at });
+        } else {
+            expiring.push(PoolTrigger { pool, time: cur_round.expires_at() });
+        }
+    }
+
+    Ok(to_binary(&QueryAnswer::CheckTriggers {
+        pools,
+        pending_reveal,
+        expiring
+    })?)
+}
+
+fn query_verify_round<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    pool: u32,
+    round: u32
+) -> ContractResult<Binary> {
+    let round_state = load_round(&deps.storage, pool, round)?;
+
+    let (seed_hash, revealed_entropy) = match (round_state.draw_version, round_state.seed_hash, round_state.revealed_entropy) {
+        (Some(_), Some(seed_hash), Some(revealed_entropy)) => (seed_hash, revealed_entropy),
+        _ => return Err(StdError::generic_err(format!("Round {} of pool {} is not finished!", round, pool)).into())
+    };
+    if round_state.draw_version != Some(DRAW_VERSION) {
+        return Err(StdError::generic_err(format!("Unknown draw version {:?}!", round_state.draw_version)).into());
+    }
+
+    let seed = draw_seed(seed_hash.as_slice(), revealed_entropy.as_bytes(), pool, round);
+    let lucky_numbers = round_state.lucky_numbers;
+    let computed_lucky_numbers = draw_lucky_numbers(seed, round_state.users_picked_numbers_count.len() as i16, lucky_numbers.len() as u8);
+    let winning_ticket = round_state.winning_ticket;
+    let computed_winning_ticket = match winning_ticket {
+        // no-loss deposits change after the draw, only the drawn ticket position can be recomputed
+        Some(_) if round_state.winner.is_some() => Some(draw_ticket(seed, round_state.tickets_count)),
+        Some(_) => draw_raffle_ticket(&deps.storage, pool, round, seed, round_state.tickets_count)?.map(|(_, ticket)| ticket),
+        None => None
+    };
+    let commitment_valid = round_state.entropy_commitment == Some(Binary(sha_256(revealed_entropy.as_bytes()).to_vec()));
+
+    Ok(to_binary(&QueryAnswer::VerifyRound {
+        pool,
+        round,
+        seed_hash,
+        revealed_entropy,
+        draw_version: DRAW_VERSION,
+        valid: commitment_valid && lucky_numbers == computed_lucky_numbers && winning_ticket == computed_winning_ticket,
+        lucky_numbers,
+        computed_lucky_numbers,
+        winning_ticket,
+        computed_winning_ticket,
+        commitment_valid
+    })?)
+}
+
+fn query_treasury<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>) -> ContractResult<Binary> {
+    let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+    let accrued: Uint128 = load(&config_data, b"treasury_accrued")?;
+    let total_collected: Uint128 = load(&config_data, b"treasury_collected")?;
+
+    Ok(to_binary(&QueryAnswer::GetTreasury {
+        treasury: load(&config_data, b"treasury")?,
+        accrued,
+        total_withdrawn: (total_collected - accrued)?,
+        total_collected
+    })?)
+}
+
+fn query_deposit<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, pool: u32, user_address_canonical: &CanonicalAddr) -> ContractResult<Binary> {
+    let pool_deposits = ReadonlyPrefixedStorage::multilevel(&[DEPOSITS, &pool.to_be_bytes()], &deps.storage);
+    Ok(to_binary(&QueryAnswer::GetDeposit {
+        pool,
+        deposit: may_load(&pool_deposits, user_address_canonical.as_slice())?.unwrap_or(Uint128(0))
+    })?)
+}
+
+/// Checks every pool parameter, shared by pool creation and tier changes
+fn validate_tier_config(tier_config: &TierConfig) -> StdResult<()> {
+    check_bounds(tier_config)?;
+    check_schedule(tier_config)?;
+    check_fees(tier_config)?;
+    check_pool_type(tier_config)
+}
+
+fn check_bounds(tier_config: &TierConfig) -> StdResult<()> {
+    if tier_config.max_rand_number < 1 || tier_config.max_rand_number > MAX_RAND_NUMBER {
+        return Err(StdError::generic_err(format!(
+            "Max random number must be between 1 and {}!", MAX_RAND_NUMBER
+        )));
+    }
+    if tier_config.min_entries < 1 {
+        return Err(StdError::generic_err("Min entries must be at least 1!"));
+    }
+    if tier_config.max_tickets_per_user == 0 {
+        return Err(StdError::generic_err("Max tickets per user must be at least 1!"));
+    }
+    // tickets are counted in entry fees, no-loss deposits included
+    if tier_config.entry_fee.u128() == 0 {
+        return Err(StdError::generic_err("Entry fee must be at least 1!"));
+    }
+    if tier_config.max_auto_winners > MAX_AUTO_WINNERS {
+        return Err(StdError::generic_err(format!(
+            "Max auto winners can't be over {}!", MAX_AUTO_WINNERS
+        )));
+    }
+    Ok(())
+}
+
+fn check_schedule(tier_config: &TierConfig) -> StdResult<()> {
+    if tier_config.betting_cutoff > tier_config.round_duration {
+        return Err(StdError::generic_err("Betting cutoff can't be longer than the round duration!"));
+    }
+    if tier_config.round_duration == 0 {
+        return Err(StdError::generic_err("Round duration must be at least 1 second!"));
+    }
+    if tier_config.round_duration > MAX_ROUND_DURATION || tier_config.round_expiry > MAX_ROUND_DURATION {
         return Err(StdError::generic_err(format!(
-            "User+VK not valid!"
+            "Round duration and round expiry can't be over {} seconds!", MAX_ROUND_DURATION
         )));
     }
+    Ok(())
+}
+
+fn check_fees(tier_config: &TierConfig) -> StdResult<()> {
+    if tier_config.triggerer_fee_bps > 10_000 {
+        return Err(StdError::generic_err("Triggerer fee can't be over 10000 bps!"));
+    }
+    if let (Some(min_triggerer_fee), Some(max_triggerer_fee)) = (tier_config.min_triggerer_fee, tier_config.max_triggerer_fee) {
+        if min_triggerer_fee > max_triggerer_fee {
+            return Err(StdError::generic_err("Min triggerer fee can't be over the max triggerer fee!"));
+        }
+    }
+    // no-loss fees come out of the yield, the other pools must be able to pay the min fee from min_entries entry fees
+    if let Some(min_triggerer_fee) = tier_config.min_triggerer_fee {
+        let no_loss = matches!(tier_config.pool_type, PoolType::NoLoss { .. });
+        if !no_loss && min_triggerer_fee > tier_config.entry_fee.multiply_ratio(tier_config.min_entries as u128, 1u128) {
+            return Err(StdError::generic_err("Min triggerer fee can't be over the entry fee times the min entries!"));
+        }
+    }
+    if tier_config.house_fee_bps > 10_000 {
+        return Err(StdError::generic_err("House fee can't be over 10000 bps!"));
+    }
+    // the treasury accrues the pool token, no-loss prizes are paid in the yield source token
+    if tier_config.house_fee_bps > 0 && matches!(tier_config.pool_type, PoolType::NoLoss { .. }) {
+        return Err(StdError::generic_err("No-loss pools can't take a house fee!"));
+    }
+    Ok(())
+}
+
+/// triggerer_fee_bps of the prize pool within the fee bounds, never more than the prize pool
+fn draw_triggerer_fee(tier_config: &TierConfig, prize_pool_size: Uint128) -> Uint128 {
+    let mut fee = prize_pool_size.multiply_ratio(tier_config.triggerer_fee_bps as u128, 10_000u128);
+    if let Some(min_triggerer_fee) = tier_config.min_triggerer_fee {
+        if fee < min_triggerer_fee {
+            fee = min_triggerer_fee;
+        }
+    }
+    if let Some(max_triggerer_fee) = tier_config.max_triggerer_fee {
+        if fee > max_triggerer_fee {
+            fee = max_triggerer_fee;
+        }
+    }
+    if fee > prize_pool_size { prize_pool_size } else { fee }
+}
+
+fn check_pool_type(tier_config: &TierConfig) -> StdResult<()> {
+    if let PoolType::Lotto { picks, brackets } = &tier_config.pool_type {
+        if *picks == 0 || *picks as i16 > tier_config.max_rand_number {
+            return Err(StdError::generic_err("Lotto picks must be between 1 and the max random number!"));
+        }
+        if brackets.is_empty() {
+            return Err(StdError::generic_err("Lotto pools need at least one prize bracket!"));
+        }
+        for (index, bracket) in brackets.iter().enumerate() {
+            if bracket.matches == 0 || bracket.matches > *picks || brackets[..index].iter().any(|other| other.matches == bracket.matches) {
+                return Err(StdError::generic_err("Lotto brackets must have distinct matches between 1 and the picks!"));
+            }
+        }
+        if brackets.iter().map(|bracket| bracket.share_bps as u32).sum::<u32>() > 10_000 {
+            return Err(StdError::generic_err("Lotto brackets shares can't be over 10000 bps!"));
+        }
+    }
+    Ok(())
+}
+
+/// Each no-loss pool needs its own yield source, the whole contract balance on it is drawn as yield
+/// so it can't be the pool token or the contract itself either
+fn check_yield_source<S: ReadonlyStorage>(storage: &S, pool: u32, tier_config: &TierConfig) -> ContractResult<()> {
+    if let PoolType::NoLoss { yield_source, .. } = &tier_config.pool_type {
+        let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, storage);
+        let token_address: HumanAddr = load(&config_data, b"token_address")?;
+        let contract_address: HumanAddr = load(&config_data, b"contract_address")?;
+        if *yield_source == token_address || *yield_source == contract_address {
+            return Err(StdError::generic_err("Yield source can't be the pool token or the contract!").into());
+        }
+
+        let pools_count: u32 = load(&config_data, b"pools_count")?;
+        for other_pool in (1..=pools_count).filter(|other_pool| *other_pool != pool) {
+            if let PoolType::NoLoss { yield_source: other_yield_source, .. } = load_tier_config(storage, other_pool)?.pool_type {
+                if other_yield_source == *yield_source {
+                    return Err(StdError::generic_err(format!(
+                        "Yield source is already used by pool {}!", other_pool
+                    )).into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn load_tier_config<S: ReadonlyStorage>(storage: &S, pool: u32) -> ContractResult<TierConfig> {
+    let pools_config = ReadonlyPrefixedStorage::new(POOLS_CONFIG, storage);
+    match may_load(&pools_config, &pool.to_be_bytes())? {
+        Some(tier_config) => Ok(tier_config),
+        None => Err(ContractError::InvalidPool { pool })
+    }
+}
+
+fn load_round<S: ReadonlyStorage>(storage: &S, pool: u32, round: u32) -> ContractResult<RoundStruct> {
+    let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], storage);
+    let pool_rounds_store = match AppendStore::<RoundStruct, _>::attach(&pool_rounds) {
+        Some(result) => result?,
+        None => return Err(ContractError::InvalidPool { pool })
+    };
+    if round >= pool_rounds_store.len() {
+        return Err(ContractError::UnknownRound { pool, round });
+    }
+    Ok(pool_rounds_store.get_at(round)?)
+}
+
+fn save_tier_config<S: Storage>(storage: &mut S, pool: u32, tier_config: &TierConfig) -> StdResult<()> {
+    let mut pools_config = PrefixedStorage::new(POOLS_CONFIG, storage);
+    save(&mut pools_config, &pool.to_be_bytes(), tier_config)
+}
+
+fn load_pool_metadata<S: ReadonlyStorage>(storage: &S, pool: u32) -> StdResult<PoolMetadata> {
+    let pools_metadata = ReadonlyPrefixedStorage::new(POOLS_METADATA, storage);
+    load(&pools_metadata, &pool.to_be_bytes())
+}
+
+fn save_pool_metadata<S: Storage>(storage: &mut S, pool: u32, metadata: &PoolMetadata) -> StdResult<()> {
+    let mut pools_metadata = PrefixedStorage::new(POOLS_METADATA, storage);
+    save(&mut pools_metadata, &pool.to_be_bytes(), metadata)
+}
+
+/// Folds the user entropy, address and block height into the running sha256 accumulator
+/// stored in config, so the draw seed depends on every user that bet or created a key.
+fn accumulate_entropy<S: Storage>(storage: &mut S, env: &Env, user_address: &HumanAddr, entropy: &[u8]) -> StdResult<()> {
+    let mut config_data = PrefixedStorage::new(CONFIG_DATA, storage);
+    let entropy_accumulator: [u8; 32] = load(&config_data, b"entropy_accumulator")?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(entropy_accumulator);
+    hasher.update(entropy);
+    hasher.update(user_address.0.as_bytes());
+    hasher.update(env.block.height.to_be_bytes());
+    let entropy_accumulator: [u8; 32] = hasher.finalize().into();
+
+    save(&mut config_data, b"entropy_accumulator", &entropy_accumulator)
+}
+
+/// Marks the unclaimed winning tickets of a round as claimed and returns their owners with their winning tickets count
+fn claim_winning_bets<S: Storage, A: Api>(storage: &mut S, api: &A, pool: u32, round: u32, lucky_number: i16) -> StdResult<Vec<(HumanAddr, u128)>> {
+    let number_bettors = ReadonlyPrefixedStorage::multilevel(&[ROUND_BETTORS, &pool.to_be_bytes(), &round.to_be_bytes(), &lucky_number.to_be_bytes()], storage);
+    let bettors: Vec<CanonicalAddr> = match AppendStore::<CanonicalAddr, _>::attach(&number_bettors) {
+        Some(store) => store?.iter().collect::<StdResult<Vec<CanonicalAddr>>>()?,
+        None => vec![]
+    };
+
+    let mut winners: Vec<(HumanAddr, u128)> = vec![];
+    for bettor in bettors {
+        // withdrawn bets are still listed, the user round bets are the source of truth
+        let mut winning_tickets: u128 = 0;
+        for (position, mut bet) in load_user_round_bets(storage, &bettor, pool, round)? {
+            if bet.numbers == [lucky_number] && !bet.claimed_reward {
+                bet.claimed_reward = true;
+                winning_tickets += 1;
+                save_user_bet(storage, &bettor, position, &bet)?;
+            }
+        }
+        // users with several winning tickets are listed once per ticket
+        if winning_tickets == 0 {
+            continue;
+        }
+        winners.push((api.human_address(&bettor)?, winning_tickets));
+    }
+
+    Ok(winners)
+}
+
+/// Sums the amounts of each recipient keeping the first appearance order, zero amounts are dropped
+fn aggregate_transfers(transfers: Vec<(HumanAddr, Uint128)>) -> Vec<(HumanAddr, Uint128)> {
+    let mut totals: Vec<(HumanAddr, Uint128)> = vec![];
+    for (recipient, amount) in transfers {
+        match totals.iter_mut().find(|(address, _)| address == &recipient) {
+            Some((_, total)) => *total += amount,
+            None => totals.push((recipient, amount))
+        }
+    }
+    totals.into_iter().filter(|(_, amount)| amount.u128() > 0).collect()
+}
+
+/// (owner, ticket, prize) of the winning tickets of a draw
+type TicketWinners = Vec<(CanonicalAddr, u32, Uint128)>;
+
+/// Marks the given (owner, ticket, prize) tickets as claimed and returns the prizes of the ones still unclaimed
+fn claim_tickets<S: Storage, A: Api>(storage: &mut S, api: &A, pool: u32, round: u32, tickets: TicketWinners) -> StdResult<Vec<(HumanAddr, Uint128)>> {
+    let mut prizes: Vec<(HumanAddr, Uint128)> = vec![];
+    for (owner, ticket, prize) in tickets {
+        match load_user_bet(storage, &owner, &bet_mapping_key(pool, round, ticket))? {
+            Some((position, mut bet)) if !bet.claimed_reward => {
+                bet.claimed_reward = true;
+                save_user_bet(storage, &owner, position, &bet)?;
+            }
+            _ => continue
+        }
+        prizes.push((api.human_address(&owner)?, prize));
+    }
+
+    Ok(prizes)
+}
+
+/// Splits a lotto round prize pool in its brackets by the matched numbers of each ticket,
+/// returns the brackets results and the winning (owner, ticket, prize) tickets
+fn draw_lotto_brackets<S: ReadonlyStorage>(
+    storage: &S,
+    pool: u32,
+    round: u32,
+    lucky_numbers: &[i16],
+    brackets: &[PrizeBracket],
+    prize_pool_size: Uint128
+) -> StdResult<(Vec<BracketResult>, TicketWinners)> {
+    let round_tickets = ReadonlyPrefixedStorage::multilevel(&[ROUND_TICKETS, &pool.to_be_bytes(), &round.to_be_bytes()], storage);
+    let tickets: Vec<RoundTicket> = match AppendStore::<RoundTicket, _>::attach(&round_tickets) {
+        Some(store) => store?.iter().collect::<StdResult<Vec<RoundTicket>>>()?,
+        None => vec![]
+    };
+
+    let ticket_brackets: Vec<Option<usize>> = tickets.iter().map(|ticket| {
+        if ticket.withdrawn {
+            return None;
+        }
+        let matches = ticket.numbers.iter().filter(|number| lucky_numbers.contains(number)).count() as u8;
+        brackets.iter().position(|bracket| bracket.matches == matches)
+    }).collect();
+
+    let results: Vec<BracketResult> = brackets.iter().enumerate().map(|(index, bracket)| {
+        let winners = ticket_brackets.iter().filter(|ticket_bracket| **ticket_bracket == Some(index)).count() as u32;
+        let bracket_pool_size = prize_pool_size.multiply_ratio(bracket.share_bps as u128, 10_000u128);
+        BracketResult {
+            matches: bracket.matches,
+            winners,
+            prize_per_ticket: if winners == 0 { Uint128(0) } else { bracket_pool_size.multiply_ratio(1u128, winners as u128) }
+        }
+    }).collect();
+
+    let winners = tickets.into_iter().zip(ticket_brackets).enumerate()
+        .filter_map(|(ticket, (round_ticket, bracket))| bracket.map(|index| (round_ticket.owner, ticket as u32, results[index].prize_per_ticket)))
+        .collect();
+
+    Ok((results, winners))
+}
+
+/// Saves a no-loss deposit balance and moves its tickets on the current round, there is a ticket per entry_fee deposited.
+/// Only the depositors holding tickets are kept on the pool depositors so the draw doesn't walk empty deposits.
+fn save_deposit<S: Storage>(
+    storage: &mut S,
+    pool: u32,
+    owner: &CanonicalAddr,
+    balance: Uint128,
+    deposit: Uint128,
+    tier_config: &TierConfig,
+    round_state: &mut RoundStruct
+) -> StdResult<()> {
+    let balance_tickets = (balance.u128() / tier_config.entry_fee.u128()) as u32;
+    let deposit_tickets = (deposit.u128() / tier_config.entry_fee.u128()) as u32;
+    round_state.tickets_count = round_state.tickets_count - balance_tickets + deposit_tickets;
+    if balance_tickets == 0 && deposit_tickets > 0 {
+        round_state.users_count += 1;
+        let mut depositors = PrefixedStorage::multilevel(&[DEPOSITORS, &pool.to_be_bytes()], storage);
+        let mut depositors_store: AppendStoreMut<CanonicalAddr, _> = AppendStoreMut::attach_or_create(&mut depositors)?;
+        let position = depositors_store.len();
+        depositors_store.push(owner)?;
+        let mut depositor_positions = PrefixedStorage::multilevel(&[DEPOSITOR_POSITIONS, &pool.to_be_bytes()], storage);
+        save(&mut depositor_positions, owner.as_slice(), &position)?;
+    } else if balance_tickets > 0 && deposit_tickets == 0 {
+        round_state.users_count -= 1;
+        remove_depositor(storage, pool, owner)?;
+    }
+
+    let mut pool_deposits = PrefixedStorage::multilevel(&[DEPOSITS, &pool.to_be_bytes()], storage);
+    save(&mut pool_deposits, owner.as_slice(), &deposit)
+}
+
+/// Removes a depositor from the pool depositors, the last depositor takes its position
+fn remove_depositor<S: Storage>(storage: &mut S, pool: u32, owner: &CanonicalAddr) -> StdResult<()> {
+    let depositor_positions = ReadonlyPrefixedStorage::multilevel(&[DEPOSITOR_POSITIONS, &pool.to_be_bytes()], storage);
+    let position: u32 = load(&depositor_positions, owner.as_slice())?;
+
+    let mut depositors = PrefixedStorage::multilevel(&[DEPOSITORS, &pool.to_be_bytes()], storage);
+    let mut depositors_store: AppendStoreMut<CanonicalAddr, _> = AppendStoreMut::attach_or_create(&mut depositors)?;
+    let last_depositor = depositors_store.pop()?;
+    if last_depositor != *owner {
+        depositors_store.set_at(position, &last_depositor)?;
+    }
+
+    let mut depositor_positions = PrefixedStorage::multilevel(&[DEPOSITOR_POSITIONS, &pool.to_be_bytes()], storage);
+    if last_depositor != *owner {
+        save(&mut depositor_positions, last_depositor.as_slice(), &position)?;
+    }
+    remove(&mut depositor_positions, owner.as_slice());
+    Ok(())
+}
+
+/// Owner of the no-loss deposit holding the ticket at the given position, tickets follow the depositors order
+/// and every depositor on the pool holds at least one ticket
+fn deposit_ticket_owner<S: ReadonlyStorage>(storage: &S, pool: u32, entry_fee: Uint128, position: u32) -> StdResult<Option<CanonicalAddr>> {
+    let depositors = ReadonlyPrefixedStorage::multilevel(&[DEPOSITORS, &pool.to_be_bytes()], storage);
+    let depositors_store = match AppendStore::<CanonicalAddr, _>::attach(&depositors) {
+        Some(result) => result?,
+        None => return Ok(None)
+    };
+    let pool_deposits = ReadonlyPrefixedStorage::multilevel(&[DEPOSITS, &pool.to_be_bytes()], storage);
+
+    let mut tickets: u128 = 0;
+    for depositor in depositors_store.iter() {
+        let depositor = depositor?;
+        let deposit: Uint128 = may_load(&pool_deposits, depositor.as_slice())?.unwrap_or(Uint128(0));
+        tickets += deposit.u128() / entry_fee.u128();
+        if tickets > position as u128 {
+            return Ok(Some(depositor));
+        }
+    }
+
+    Ok(None)
+}
+
+/// No-loss pools read the contract yield with the token viewing key, so it is set on their yield source
+fn yield_source_key_msgs<S: ReadonlyStorage>(storage: &S, tier_config: &TierConfig) -> StdResult<Vec<CosmosMsg>> {
+    match &tier_config.pool_type {
+        PoolType::NoLoss { yield_source, yield_source_hash } => {
+            let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, storage);
+            let token_vk: String = load(&config_data, b"token_vk")?;
+            Ok(vec![snip20::set_viewing_key_msg(token_vk, None, BLOCK_SIZE, yield_source_hash.clone(), yield_source.clone())?])
+        }
+        _ => Ok(vec![])
+    }
+}
+
+/// Winning (owner, ticket) of a raffle round, drawn among the tickets that were not withdrawn
+fn draw_raffle_ticket<S: ReadonlyStorage>(storage: &S, pool: u32, round: u32, seed: [u8; 32], tickets_count: u32) -> StdResult<Option<(CanonicalAddr, u32)>> {
+    if tickets_count == 0 {
+        return Ok(None);
+    }
+    let position = draw_ticket(seed, tickets_count) as usize;
+
+    let round_tickets = ReadonlyPrefixedStorage::multilevel(&[ROUND_TICKETS, &pool.to_be_bytes(), &round.to_be_bytes()], storage);
+    let round_tickets_store = match AppendStore::<RoundTicket, _>::attach(&round_tickets) {
+        Some(result) => result?,
+        None => return Ok(None)
+    };
+    let winner = round_tickets_store.iter().enumerate()
+        .filter(|(_, round_ticket)| !matches!(round_ticket, Ok(RoundTicket { withdrawn: true, .. })))
+        .nth(position);
+
+    match winner {
+        Some((ticket, round_ticket)) => Ok(Some((round_ticket?.owner, ticket as u32))),
+        None => Ok(None)
+    }
+}
+
+/// One SNIP-20 transfer per recipient with the sum of all its amounts
+fn aggregated_transfer_msgs(transfers: Vec<(HumanAddr, Uint128)>, token_hash: &str, token_address: &HumanAddr) -> StdResult<Vec<CosmosMsg>> {
+    aggregate_transfers(transfers).into_iter()
+        .map(|(recipient, amount)| transfer_msg(recipient, amount, None, BLOCK_SIZE, token_hash.to_string(), token_address.clone()))
+        .collect()
+}
+
+fn check_trigger_permission<S: ReadonlyStorage>(config_data: &S, sender: &HumanAddr) -> ContractResult<()> {
+    let trigger_mode: TriggerMode = load(config_data, b"trigger_mode")?;
+    let triggerer_address: HumanAddr = load(config_data, b"triggerer")?;
+    if trigger_mode == TriggerMode::Triggerer && &triggerer_address != sender {
+        return Err(ContractError::Unauthorized { action: "trigger the draws".to_string() });
+    }
+    Ok(())
+}
+
+/// A round with an entropy commitment is closed for betting and withdrawals until the
+/// commitment is revealed or `reveal_timeout` blocks have passed since it was made.
+fn is_round_locked(round: &RoundStruct, block_height: u64, reveal_timeout: u64) -> bool {
+    match round.commitment_height {
+        Some(commitment_height) => block_height <= commitment_height + reveal_timeout,
+        None => false
+    }
+}
+
+/// Cancels the current round of a pool once it expired without min_entries and opens the next one with tier_config
+/// with the pool size carried over, the cancelled round bets are refunded with ClaimRefund.
+fn cancel_expired_round<S: Storage>(
+    pool_rounds_store: &mut AppendStoreMut<RoundStruct, S>,
+    tier_config: &TierConfig,
+    env: &Env,
+    reveal_timeout: u64
+) -> StdResult<bool> {
+    let mut cur_round: RoundStruct = pool_rounds_store.get_at(pool_rounds_store.len() - 1)?;
+    if !cur_round.is_expired(env.block.time) || is_round_locked(&cur_round, env.block.height, reveal_timeout) {
+        return Ok(false);
+    }
+
+    let carried_pool_size = cur_round.carried_pool_size;
+    cur_round.cancelled = true;
+    cur_round.pool_size = (cur_round.pool_size - carried_pool_size)?;
+    cur_round.carried_pool_size = Uint128(0);
+    cur_round.entries_pool_size = cur_round.pool_size;
+    cur_round.round_end_timestamp = Some(env.block.time);
+    pool_rounds_store.set_at(pool_rounds_store.len() - 1, &cur_round)?;
+
+    pool_rounds_store.push(&cur_round.next(carried_pool_size, tier_config, env.block.time))?;
+    Ok(true)
+}
+
+fn join_pools(pools: &[u32]) -> String {
+    pools.iter().map(|pool| pool.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn bet_mapping_key(pool: u32, round: u32, ticket: u32) -> String {
+    "pool".to_owned() + &pool.to_string() + "_" + "round" + &round.to_string() + "_" + "ticket" + &ticket.to_string()
+}
+
+/// Adds a bet to the owner bets and its pool round and bet key indexes, returns its position
+fn push_user_bet<S: Storage>(storage: &mut S, owner: &CanonicalAddr, bet: &UserBetStruct) -> StdResult<u32> {
+    let mut user_bets = PrefixedStorage::multilevel(&[USER_BETS, owner.as_slice()], storage);
+    let mut user_bets_store: AppendStoreMut<UserBetStruct, _> = AppendStoreMut::attach_or_create(&mut user_bets)?;
+    let position = user_bets_store.len();
+    user_bets_store.push(bet)?;
+
+    let mut user_bet_keys = PrefixedStorage::multilevel(&[USER_BET_KEYS, owner.as_slice()], storage);
+    save(&mut user_bet_keys, bet_mapping_key(bet.pool, bet.round_number, bet.ticket).as_bytes(), &position)?;
+
+    let mut user_round_bets = PrefixedStorage::multilevel(&[USER_ROUND_BETS, owner.as_slice(), &bet.pool.to_be_bytes(), &bet.round_number.to_be_bytes()], storage);
+    AppendStoreMut::<u32, _>::attach_or_create(&mut user_round_bets)?.push(&position)?;
+    Ok(position)
+}
+
+fn save_user_bet<S: Storage>(storage: &mut S, owner: &CanonicalAddr, position: u32, bet: &UserBetStruct) -> StdResult<()> {
+    let mut user_bets = PrefixedStorage::multilevel(&[USER_BETS, owner.as_slice()], storage);
+    AppendStoreMut::<UserBetStruct, _>::attach_or_create(&mut user_bets)?.set_at(position, bet)
+}
+
+fn load_user_bet<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, key: &str) -> StdResult<Option<(u32, UserBetStruct)>> {
+    let user_bet_keys = ReadonlyPrefixedStorage::multilevel(&[USER_BET_KEYS, owner.as_slice()], storage);
+    let position: u32 = match may_load(&user_bet_keys, key.as_bytes())? {
+        Some(position) => position,
+        None => return Ok(None)
+    };
+    let user_bets = ReadonlyPrefixedStorage::multilevel(&[USER_BETS, owner.as_slice()], storage);
+    match AppendStore::<UserBetStruct, _>::attach(&user_bets) {
+        Some(store) => Ok(Some((position, store?.get_at(position)?))),
+        None => Ok(None)
+    }
+}
+
+/// Bets of the owner on a pool round with their positions, withdrawn bets are not listed
+fn load_user_round_bets<S: ReadonlyStorage>(storage: &S, owner: &CanonicalAddr, pool: u32, round: u32) -> StdResult<Vec<(u32, UserBetStruct)>> {
+    let user_round_bets = ReadonlyPrefixedStorage::multilevel(&[USER_ROUND_BETS, owner.as_slice(), &pool.to_be_bytes(), &round.to_be_bytes()], storage);
+    let positions: Vec<u32> = match AppendStore::<u32, _>::attach(&user_round_bets) {
+        Some(store) => store?.iter().collect::<StdResult<Vec<u32>>>()?,
+        None => return Ok(vec![])
+    };
+    let user_bets = ReadonlyPrefixedStorage::multilevel(&[USER_BETS, owner.as_slice()], storage);
+    let user_bets_store = match AppendStore::<UserBetStruct, _>::attach(&user_bets) {
+        Some(store) => store?,
+        None => return Ok(vec![])
+    };
+    positions.into_iter().map(|position| Ok((position, user_bets_store.get_at(position)?))).collect()
+}
+
+fn clear_user_round_bets<S: Storage>(storage: &mut S, owner: &CanonicalAddr, pool: u32, round: u32) -> StdResult<()> {
+    let mut user_round_bets = PrefixedStorage::multilevel(&[USER_ROUND_BETS, owner.as_slice(), &pool.to_be_bytes(), &round.to_be_bytes()], storage);
+    let mut user_round_bets_store: AppendStoreMut<u32, _> = AppendStoreMut::attach_or_create(&mut user_round_bets)?;
+    while !user_round_bets_store.is_empty() {
+        user_round_bets_store.pop()?;
+    }
+    Ok(())
+}
+
+fn is_key_valid<S: ReadonlyStorage>(
+    storage: &S,
+    address: &CanonicalAddr,
+    viewing_key: String,
+) -> StdResult<bool> {
+    // load the address' key
+    let read_key = ReadonlyPrefixedStorage::new(PREFIX_VIEW_KEY, storage);
+    let load_key: Option<[u8; VIEWING_KEY_SIZE]> = may_load(&read_key, address.as_slice())?;
+    let input_key = ViewingKey(viewing_key);
+    // if a key was set
+    if let Some(expected_key) = load_key {
+        // and it matches
+        if input_key.check_viewing_key(&expected_key) {
+            return Ok(true);
+        }
+    } else {
+        // Checking the key will take significant time. We don't want to exit immediately if it isn't set
+        // in a way which will allow to time the command and determine if a viewing key doesn't exist
+        input_key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
+    }
+    Ok(false)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{QuerierResult, WasmMsg};
+    use crate::msg::JackpotConfig;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+
+    fn init_helper() -> Extern<MockStorage, MockApi, MockQuerier> {
+        // long enough for the secret1 addresses of permit signers
+        let mut deps = mock_dependencies(45, &[]);
+        let env = mock_env("admin", &[]);
+        let init_msg = InitMsg {
+            entropy: 1234,
+            triggerer_address: HumanAddr::from("triggerer"),
+            token_address: HumanAddr::from("token"),
+            token_hash: "token_hash".to_string(),
+            token_vk: "token_vk".to_string(),
+            reveal_timeout: 10,
+            trigger_mode: None,
+            treasury: None,
+        };
+        init(&mut deps, env, init_msg).unwrap();
+        deps
+    }
 
-    let bets_storage = ReadonlyPrefixedStorage::new(BETS, &deps.storage);
-    let user_bets_store: Option<UserBetsStruct> = load(&bets_storage, &user_address_canonical.as_slice())?;
+    fn tier_config_helper(entry_fee: u128, min_entries: i16, max_rand_number: i16) -> TierConfig {
+        TierConfig {
+            entry_fee: Uint128(entry_fee),
+            triggerer_fee_bps: 0,
+            min_triggerer_fee: Some(Uint128(entry_fee / 2)),
+            max_triggerer_fee: None,
+            min_entries,
+            max_rand_number,
+            round_duration: 3600,
+            betting_cutoff: 600,
+            round_expiry: 3600,
+            auto_distribute: false,
+            max_auto_winners: 0,
+            max_tickets_per_user: 1,
+            pool_type: PoolType::LuckyNumber,
+            jackpot: None,
+            house_fee_bps: 0,
+        }
+    }
 
-    if user_bets_store == None {
-        return to_binary(&QueryAnswer::GetUserBets {
-            user_bets
-        })
-    } else {
-        let user_bets_store_unwrapped = user_bets_store.unwrap();
-        for key in keys {
-            let bet_state = user_bets_store_unwrapped.bets.get(&key);
-            if bet_state != None {
-                user_bets.push(bet_state.unwrap().to_owned());
-            }
+    fn create_pool_msg(tier_config: TierConfig) -> HandleMsg {
+        HandleMsg::CreatePool {
+            name: format!("Pool {}", tier_config.max_rand_number),
+            description: "Test pool".to_string(),
+            display_order: tier_config.max_rand_number as u32,
+            entry_fee: tier_config.entry_fee,
+            triggerer_fee_bps: tier_config.triggerer_fee_bps,
+            min_triggerer_fee: tier_config.min_triggerer_fee,
+            max_triggerer_fee: tier_config.max_triggerer_fee,
+            min_entries: tier_config.min_entries,
+            max_rand_number: tier_config.max_rand_number,
+            round_duration: tier_config.round_duration,
+            betting_cutoff: tier_config.betting_cutoff,
+            round_expiry: tier_config.round_expiry,
+            auto_distribute: tier_config.auto_distribute,
+            max_auto_winners: tier_config.max_auto_winners,
+            max_tickets_per_user: tier_config.max_tickets_per_user,
+            pool_type: tier_config.pool_type,
+            jackpot: tier_config.jackpot,
+            house_fee_bps: tier_config.house_fee_bps,
         }
     }
 
-    return to_binary(&QueryAnswer::GetUserBets {
-        user_bets
-    })
-}
+    fn change_tier_msg(pool: u32, tier_config: TierConfig) -> HandleMsg {
+        HandleMsg::ChangeTier {
+            pool,
+            entry_fee: tier_config.entry_fee,
+            triggerer_fee_bps: tier_config.triggerer_fee_bps,
+            min_triggerer_fee: tier_config.min_triggerer_fee,
+            max_triggerer_fee: tier_config.max_triggerer_fee,
+            min_entries: tier_config.min_entries,
+            max_rand_number: tier_config.max_rand_number,
+            round_duration: tier_config.round_duration,
+            betting_cutoff: tier_config.betting_cutoff,
+            round_expiry: tier_config.round_expiry,
+            auto_distribute: tier_config.auto_distribute,
+            max_auto_winners: tier_config.max_auto_winners,
+            max_tickets_per_user: tier_config.max_tickets_per_user,
+            pool_type: tier_config.pool_type,
+            jackpot: tier_config.jackpot,
+            house_fee_bps: tier_config.house_fee_bps,
+        }
+    }
 
-fn query_paginated_user_bets<S: Storage, A: Api, Q: Querier>(deps: &Extern<S, A, Q>, 
-    user_address: HumanAddr, 
-    viewing_key: String,
-    page: u32,
-    page_size: u32
-) -> QueryResult  {
-    let mut user_bets : Vec<UserBetStruct> = vec![];
-    let mut bet_rounds: Vec<RoundStruct> = vec![];
-    let mut user_bets_total_count = 0;
+    fn create_pool_with(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, tier_config: TierConfig) -> StdResult<HandleResponse> {
+        handle(deps, mock_env("admin", &[]), create_pool_msg(tier_config))
+    }
 
-    let user_address_canonical = &deps.api.canonical_address(&user_address)?;
-    if !is_key_valid(&deps.storage, user_address_canonical, viewing_key)? {
-        return Err(StdError::generic_err(format!(
-            "User+VK not valid!"
-        )));
+    fn create_pool_helper(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, entry_fee: u128, min_entries: i16, max_rand_number: i16) -> StdResult<HandleResponse> {
+        create_pool_with(deps, tier_config_helper(entry_fee, min_entries, max_rand_number))
     }
 
-    let tier1_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &deps.storage);
-    let tier1_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier1_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetPaginatedUserBets {
-            user_bets,
-            bet_rounds,
-            user_bets_total_count
+    fn bet_helper(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, user: &str, amount: u128, pool: u32, number: i16) -> StdResult<HandleResponse> {
+        handle(deps, mock_env("token", &[]), HandleMsg::Receive {
+            sender: HumanAddr::from(user),
+            from: HumanAddr::from(user),
+            amount: Uint128(amount),
+            msg: to_binary(&HandleMsg::Bet { pool, numbers: vec![number], entropy: Some(user.to_string()), padding: None }).unwrap(),
         })
-    };
-    let tier2_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &deps.storage);
-    let tier2_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier2_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetPaginatedUserBets {
-            user_bets,
-            bet_rounds,
-            user_bets_total_count
-        })
-    };
-    let tier3_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &deps.storage);
-    let tier3_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier3_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetPaginatedUserBets {
-            user_bets,
-            bet_rounds,
-            user_bets_total_count
-        })
-    };
-    let bets_storage = ReadonlyPrefixedStorage::new(BETS, &deps.storage);
-    let user_bets_store: Option<UserBetsStruct> = load(&bets_storage, &user_address_canonical.as_slice())?;
-
-    if user_bets_store == None {
-        return to_binary(&QueryAnswer::GetPaginatedUserBets {
-            user_bets,
-            bet_rounds,
-            user_bets_total_count
-        })
-    } else {
-        let user_bets_store_unwrapped = user_bets_store.unwrap();
-        user_bets_total_count = user_bets_store_unwrapped.bet_keys.len();
-        let user_bet_keys_iter = user_bets_store_unwrapped.bet_keys
-        .iter()
-        .rev()
-        .skip((page * page_size) as _)
-        .take(page_size as _);
-
-        let user_bet_keys: Vec<&String> = user_bet_keys_iter.collect();
-        for bet_key in user_bet_keys { 
-            let bet_state: &UserBetStruct = &user_bets_store_unwrapped.bets[bet_key];
-            user_bets.push(bet_state.clone());
-            if bet_state.tier == 1 {
-                let mut round_state = tier1_rounds_store.get_at(bet_state.round_number).unwrap();
-                round_state.users_picked_numbers_count = vec![];
-                bet_rounds.push(round_state)
-            }
-            if bet_state.tier == 2 {
-                let mut round_state = tier2_rounds_store.get_at(bet_state.round_number).unwrap();
-                round_state.users_picked_numbers_count = vec![];
-                bet_rounds.push(round_state)
+    }
+
+    // one block per minute after the mock env time
+    fn env_at_height(sender: &str, height: u64) -> Env {
+        let mut env = mock_env(sender, &[]);
+        env.block.height = height;
+        env.block.time += height * 60;
+        env
+    }
+
+    fn commitment(entropy: &str) -> Binary {
+        Binary(sha_256(entropy.as_bytes()).to_vec())
+    }
+
+    fn paginated_user_bets(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, user: &str) -> (Vec<UserBetStruct>, usize) {
+        handle(deps, mock_env(user, &[]), HandleMsg::SetViewingKey { key: format!("{}_key", user), padding: None }).unwrap();
+        let res = query(deps, QueryMsg::GetPaginatedUserBets { user_address: HumanAddr::from(user), viewing_key: format!("{}_key", user), page: 0, page_size: 10 }).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::GetPaginatedUserBets { user_bets, user_bets_total_count, .. } => (user_bets, user_bets_total_count),
+            _ => panic!("unexpected query answer"),
+        }
+    }
+
+    fn assert_contract_error<T: std::fmt::Debug>(result: StdResult<T>, error: ContractError) {
+        match result {
+            Err(StdError::GenericErr { msg, .. }) => assert_eq!(msg, error.to_string()),
+            other => panic!("expected {:?}, got {:?}", error, other),
+        }
+    }
+
+    fn current_round(deps: &Extern<MockStorage, MockApi, MockQuerier>, pool: u32) -> RoundStruct {
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &pool.to_be_bytes()], &deps.storage);
+        let pool_rounds_store = AppendStore::<RoundStruct, _>::attach(&pool_rounds).unwrap().unwrap();
+        pool_rounds_store.get_at(pool_rounds_store.len() - 1).unwrap()
+    }
+
+    #[test]
+    fn test_create_pools() {
+        let mut deps = init_helper();
+
+        let res = create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CreatePool { pool: 1 }));
+        create_pool_helper(&mut deps, 500, 3, 10).unwrap();
+
+        let not_admin = handle(&mut deps, mock_env("user", &[]), create_pool_msg(tier_config_helper(1, 1, 1)));
+        assert!(not_admin.is_err());
+
+        let round = current_round(&deps, 2);
+        assert_eq!(round.pool, 2);
+        assert_eq!(round.users_picked_numbers_count.len(), 10);
+
+        let res = query(&deps, QueryMsg::GetTierConfigs { pools: vec![1, 2] }).unwrap();
+        let answer: QueryAnswer = from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap();
+        match answer {
+            QueryAnswer::GetTierConfigs { tier_configs } => {
+                assert_eq!(tier_configs.len(), 2);
+                assert_eq!(tier_configs[1].pool, 2);
+                assert_eq!(tier_configs[1].config.entry_fee, Uint128(500));
             }
-            if bet_state.tier == 3 {
-                let mut round_state = tier3_rounds_store.get_at(bet_state.round_number).unwrap();
-                round_state.users_picked_numbers_count = vec![];
-                bet_rounds.push(round_state)
+            _ => panic!("unexpected query answer"),
+        }
+        assert!(query(&deps, QueryMsg::GetTierConfigs { pools: vec![3] }).is_err());
+    }
+
+    #[test]
+    fn test_validate_tier_config() {
+        let mut deps = init_helper();
+
+        assert!(create_pool_helper(&mut deps, 1000, 2, 0).is_err());
+        assert!(create_pool_helper(&mut deps, 1000, 2, -5).is_err());
+        assert!(create_pool_helper(&mut deps, 1000, 2, MAX_RAND_NUMBER + 1).is_err());
+        assert!(create_pool_helper(&mut deps, 1000, 0, 5).is_err());
+        create_pool_helper(&mut deps, 1000, 2, MAX_RAND_NUMBER).unwrap();
+
+        let change_tier = |min_entries: i16, max_rand_number: i16, round_duration: u64, max_tickets_per_user: u32| change_tier_msg(1, TierConfig {
+            round_duration,
+            betting_cutoff: 0,
+            max_tickets_per_user,
+            ..tier_config_helper(1000, min_entries, max_rand_number)
+        });
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier(2, 0, 3600, 1)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier(-1, 5, 3600, 1)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier(2, 5, 0, 1)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier(2, 5, 3600, 0)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier(2, 5, MAX_ROUND_DURATION + 1, 1)).is_err());
+        handle(&mut deps, mock_env("admin", &[]), change_tier(2, 5, MAX_ROUND_DURATION, 1)).unwrap();
+        handle(&mut deps, mock_env("admin", &[]), change_tier(2, 5, 3600, 1)).unwrap();
+
+        let change_limits = |entry_fee: u128, round_expiry: u64, max_auto_winners: u32| change_tier_msg(1, TierConfig {
+            min_triggerer_fee: None,
+            betting_cutoff: 0,
+            round_expiry,
+            auto_distribute: true,
+            max_auto_winners,
+            ..tier_config_helper(entry_fee, 2, 5)
+        });
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_limits(0, 3600, 10)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_limits(1000, u64::MAX, 10)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_limits(1000, 3600, MAX_AUTO_WINNERS + 1)).is_err());
+        handle(&mut deps, mock_env("admin", &[]), change_limits(1000, MAX_ROUND_DURATION, MAX_AUTO_WINNERS)).unwrap();
+
+        for reveal_timeout in [0, MAX_REVEAL_TIMEOUT + 1] {
+            let mut bad_init_deps = mock_dependencies(20, &[]);
+            assert!(init(&mut bad_init_deps, mock_env("admin", &[]), InitMsg {
+                entropy: 1234,
+                triggerer_address: HumanAddr::from("triggerer"),
+                token_address: HumanAddr::from("token"),
+                token_hash: "token_hash".to_string(),
+                token_vk: "token_vk".to_string(),
+                reveal_timeout,
+                trigger_mode: None,
+                treasury: None,
+            }).is_err());
+        }
+    }
+
+    #[test]
+    fn test_change_tier_next_round() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 3).unwrap();
+
+        handle(&mut deps, mock_env("admin", &[]), change_tier_msg(1, tier_config_helper(2000, 2, 10))).unwrap();
+
+        // the open round keeps its entry fee and numbers range
+        assert!(bet_helper(&mut deps, "bob", 2000, 1, 3).is_err());
+        assert!(bet_helper(&mut deps, "bob", 1000, 1, 8).is_err());
+        bet_helper(&mut deps, "bob", 1000, 1, 3).unwrap();
+        bet_helper(&mut deps, "carol", 1000, 1, 4).unwrap();
+        assert_eq!(current_round(&deps, 1).tier_config.entry_fee, Uint128(1000));
+
+        // refunds use the entry fee the tickets were bought with
+        let res = handle(&mut deps, mock_env("carol", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("carol"), Uint128(1000), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+
+        let round = current_round(&deps, 1);
+        assert_eq!(round.round_number, 1);
+        assert_eq!(round.tier_config.entry_fee, Uint128(2000));
+        assert_eq!(round.users_picked_numbers_count.len(), 10);
+        assert!(bet_helper(&mut deps, "alice", 1000, 1, 8).is_err());
+        bet_helper(&mut deps, "alice", 2000, 1, 8).unwrap();
+    }
+
+    #[test]
+    fn test_pools_metadata() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 30).unwrap();
+        create_pool_helper(&mut deps, 500, 2, 5).unwrap();
+
+        handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangePoolMetadata {
+            pool: 2,
+            name: "5 min".to_string(),
+            description: "1 - 5".to_string(),
+            display_order: 1,
+        }).unwrap();
+        assert!(handle(&mut deps, mock_env("user", &[]), HandleMsg::ChangePoolMetadata {
+            pool: 2,
+            name: "".to_string(),
+            description: "".to_string(),
+            display_order: 0,
+        }).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangePoolMetadata {
+            pool: 3,
+            name: "".to_string(),
+            description: "".to_string(),
+            display_order: 0,
+        }).is_err());
+
+        let res = query(&deps, QueryMsg::GetPools {}).unwrap();
+        let answer: QueryAnswer = from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap();
+        match answer {
+            QueryAnswer::GetPools { pools } => {
+                assert_eq!(pools.len(), 2);
+                assert_eq!(pools[0].pool, 2);
+                assert_eq!(pools[0].name, "5 min");
+                assert_eq!(pools[0].config.max_rand_number, 5);
+                assert_eq!(pools[1].pool, 1);
             }
+            _ => panic!("unexpected query answer"),
         }
     }
-  
-    to_binary(&QueryAnswer::GetPaginatedUserBets {
-        user_bets,
-        bet_rounds,
-        user_bets_total_count
-    })
-}
 
+    #[test]
+    fn test_bet_and_trigger_pools() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 1).unwrap();
+        create_pool_helper(&mut deps, 500, 2, 1).unwrap();
+
+        assert!(bet_helper(&mut deps, "alice", 1000, 3, 1).is_err());
+        assert!(bet_helper(&mut deps, "alice", 500, 1, 1).is_err());
+        assert!(bet_helper(&mut deps, "alice", 1000, 1, 2).is_err());
+
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "alice", 500, 2, 1).unwrap();
+        assert!(bet_helper(&mut deps, "alice", 1000, 1, 1).is_err());
+
+        let res = query(&deps, QueryMsg::CheckTriggers {}).unwrap();
+        let answer: QueryAnswer = from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap();
+        match answer {
+            QueryAnswer::CheckTriggers { pools, .. } => assert_eq!(pools, vec![PoolTrigger { pool: 1, time: current_round(&deps, 1).closes_at }]),
+            _ => panic!("unexpected query answer"),
+        }
 
-fn query_paginated_rounds<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    tier1: bool,
-    tier2: bool,
-    tier3: bool,
-    page: u32,
-    page_size: u32,
-) -> StdResult<Binary> {
-    let mut tier1_rounds: Option<Vec<RoundStruct>> = None;
-    let mut tier2_rounds: Option<Vec<RoundStruct>> = None;
-    let mut tier3_rounds: Option<Vec<RoundStruct>> = None;
+        let not_triggerer = handle(&mut deps, env_at_height("alice", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") });
+        assert!(not_triggerer.is_err());
 
-    if tier1 {
-        let tier1_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &deps.storage);
-        let tier1_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier1_rounds_storage) {
-            result?
-        } else {
-            return to_binary(&QueryAnswer::GetPaginatedRounds {
-                tier1_rounds,
-                tier2_rounds,
-                tier3_rounds
-            })
+        let res = handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools == vec![1]));
+
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2], entropy: "secret".to_string() }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+
+        // pool 1 moved to the next round, pool 2 did not have enough entries
+        assert_eq!(current_round(&deps, 1).round_number, 1);
+        assert_eq!(current_round(&deps, 2).round_number, 0);
+
+        let res = handle(&mut deps, env_at_height("bob", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(handle(&mut deps, env_at_height("bob", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+    }
+
+    #[test]
+    fn test_entropy_accumulator() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+
+        let accumulator = |deps: &Extern<MockStorage, MockApi, MockQuerier>| -> [u8; 32] {
+            let config_data = ReadonlyPrefixedStorage::new(CONFIG_DATA, &deps.storage);
+            load(&config_data, b"entropy_accumulator").unwrap()
         };
 
-        let rounds_iter = tier1_rounds_store
-        .iter()
-        .rev()
-        .skip((page * page_size) as _)
-        .take(page_size as _)
-        .map(|x| {
-            let mut modified = x.unwrap();
-            modified.users_picked_numbers_count = vec![];
-            return modified
-        });
-        
-        let rounds: Vec<RoundStruct> = rounds_iter.collect();
-        tier1_rounds = Some(rounds);
+        let initial = accumulator(&deps);
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        let after_bet = accumulator(&deps);
+        assert_ne!(initial, after_bet);
+
+        handle(&mut deps, mock_env("bob", &[]), HandleMsg::CreateViewingKey { entropy: "bob".to_string(), padding: None }).unwrap();
+        assert_ne!(after_bet, accumulator(&deps));
     }
 
-    if tier2 {
-        let tier2_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &deps.storage);
-        let tier2_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier2_rounds_storage) {
-            result?
-        } else {
-            return to_binary(&QueryAnswer::GetPaginatedRounds {
-                tier1_rounds,
-                tier2_rounds,
-                tier3_rounds
-            })
+    #[test]
+    fn test_entropy_snapshot() {
+        let draw = |grind: bool| -> i16 {
+            let mut deps = init_helper();
+            create_pool_helper(&mut deps, 1000, 1, 1000).unwrap();
+            bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+            handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+
+            // the seed is taken on the commitment and stays private until the draw
+            let res = query(&deps, QueryMsg::GetRounds { rounds: vec![PoolRound { pool: 1, round: 0 }] }).unwrap();
+            match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+                QueryAnswer::GetRounds { rounds } => assert_eq!(rounds[0].seed_hash, None),
+                _ => panic!("unexpected"),
+            }
+            assert!(current_round(&deps, 1).seed_hash.is_some());
+
+            // the committer changing the contract entropy before the reveal doesn't change the draw
+            if grind {
+                handle(&mut deps, mock_env("triggerer", &[]), HandleMsg::CreateViewingKey { entropy: "grind".to_string(), padding: None }).unwrap();
+            }
+            handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+            load_round(&deps.storage, 1, 0).unwrap().lucky_number.unwrap()
         };
+        assert_eq!(draw(false), draw(true));
+    }
 
-        let rounds_iter = tier2_rounds_store
-        .iter()
-        .rev()
-        .skip((page * page_size) as _)
-        .take(page_size as _)
-        .map(|x| {
-            let mut modified = x.unwrap();
-            modified.users_picked_numbers_count = vec![];
-            return modified
+    #[test]
+    fn test_commit_reveal() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 2).unwrap();
+
+        assert!(handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: Binary(vec![1, 2, 3]) }).is_err());
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        assert!(handle(&mut deps, env_at_height("triggerer", 105), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("other") }).is_err());
+
+        // the round is closed for betting and withdrawals while the commitment is pending
+        assert!(handle(&mut deps, env_at_height("token", 101), HandleMsg::Receive {
+            sender: HumanAddr::from("carol"),
+            from: HumanAddr::from("carol"),
+            amount: Uint128(1000),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers: vec![3], entropy: None, padding: None }).unwrap(),
+        }).is_err());
+        assert!(handle(&mut deps, env_at_height("alice", 101), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+
+        // reveals must happen after the commitment block and match it
+        assert!(handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).is_err());
+        assert!(handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "wrong".to_string() }).is_err());
+
+        // once expired the reveal is rejected and the round reopens
+        assert!(handle(&mut deps, env_at_height("triggerer", 111), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).is_err());
+        handle(&mut deps, env_at_height("alice", 111), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(current_round(&deps, 1).users_count, 1);
+        assert_eq!(current_round(&deps, 1).lucky_number, None);
+    }
+
+    #[test]
+    fn test_verify_round() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 3).unwrap();
+
+        assert!(query(&deps, QueryMsg::VerifyRound { pool: 1, round: 0 }).is_err());
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        assert!(res.log.iter().any(|attr| attr.key == "lucky_number"));
+
+        let res = query(&deps, QueryMsg::VerifyRound { pool: 1, round: 0 }).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::VerifyRound { revealed_entropy, lucky_numbers, computed_lucky_numbers, commitment_valid, valid, .. } => {
+                assert_eq!(revealed_entropy, "secret");
+                assert_eq!(lucky_numbers, computed_lucky_numbers);
+                assert!(commitment_valid);
+                assert!(valid);
+            }
+            _ => panic!("unexpected"),
+        }
+
+        // the unfinished next round can't be verified yet
+        assert!(query(&deps, QueryMsg::VerifyRound { pool: 1, round: 1 }).is_err());
+    }
+
+    #[test]
+    fn test_round_schedule() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 5).unwrap();
+        let round = current_round(&deps, 1);
+        assert_eq!(round.closes_at, round.opens_at + 3600);
+
+        // bets stop 10 minutes before the round closes
+        handle(&mut deps, env_at_height("token", 49), HandleMsg::Receive {
+            sender: HumanAddr::from("alice"),
+            from: HumanAddr::from("alice"),
+            amount: Uint128(1000),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers: vec![1], entropy: None, padding: None }).unwrap(),
+        }).unwrap();
+        assert!(handle(&mut deps, env_at_height("token", 51), HandleMsg::Receive {
+            sender: HumanAddr::from("bob"),
+            from: HumanAddr::from("bob"),
+            amount: Uint128(1000),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers: vec![2], entropy: None, padding: None }).unwrap(),
+        }).is_err());
+
+        // the round is filled but can't be drawn before it closes
+        let res = query(&deps, QueryMsg::CheckTriggers {}).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::CheckTriggers { pools, .. } => assert_eq!(pools, vec![PoolTrigger { pool: 1, time: env_at_height("triggerer", 60).block.time }]),
+            _ => panic!("unexpected query answer"),
+        }
+        let res = handle(&mut deps, env_at_height("triggerer", 59), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools.is_empty()));
+
+        handle(&mut deps, env_at_height("triggerer", 60), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let trigger_env = env_at_height("triggerer", 61);
+        handle(&mut deps, trigger_env.clone(), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+
+        // the next round is scheduled from the draw time
+        let round = current_round(&deps, 1);
+        assert_eq!(round.opens_at, trigger_env.block.time);
+        assert_eq!(round.closes_at, trigger_env.block.time + 3600);
+    }
+
+    #[test]
+    fn test_bets_until_min_entries() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        let bet_at = |user: &str, height: u64| (env_at_height("token", height), HandleMsg::Receive {
+            sender: HumanAddr::from(user),
+            from: HumanAddr::from(user),
+            amount: Uint128(1000),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers: vec![1], entropy: None, padding: None }).unwrap(),
         });
 
-        let rounds: Vec<RoundStruct> = rounds_iter.collect();
-        tier2_rounds = Some(rounds);
+        // the round closed with one of its two min entries, it keeps taking bets until it has them
+        let (env, msg) = bet_at("alice", 49);
+        handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = bet_at("bob", 70);
+        handle(&mut deps, env, msg).unwrap();
+        let (env, msg) = bet_at("carol", 70);
+        assert!(handle(&mut deps, env, msg).is_err());
+        assert_eq!(current_round(&deps, 1).tickets_count, 2);
+
+        let res = handle(&mut deps, env_at_height("triggerer", 70), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools == vec![1]));
     }
 
-    if tier3 {
-        let tier3_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &deps.storage);
-        let tier3_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier3_rounds_storage) {
-            result?
-        } else {
-            return to_binary(&QueryAnswer::GetPaginatedRounds {
-                tier1_rounds,
-                tier2_rounds,
-                tier3_rounds
-            })
+    #[test]
+    fn test_refund_expired_round() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+
+        // the round closed with a single entry but did not expire yet
+        assert!(handle(&mut deps, env_at_height("alice", 119), HandleMsg::ClaimRefund { pool: 1, round: 0, padding: None }).is_err());
+        let res = query(&deps, QueryMsg::CheckTriggers {}).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::CheckTriggers { pools, expiring, .. } => {
+                assert!(pools.is_empty());
+                assert_eq!(expiring, vec![PoolTrigger { pool: 1, time: env_at_height("triggerer", 120).block.time }]);
+            }
+            _ => panic!("unexpected query answer"),
+        }
+
+        assert!(handle(&mut deps, env_at_height("bob", 121), HandleMsg::ClaimRefund { pool: 1, round: 0, padding: None }).is_err());
+        let res = handle(&mut deps, env_at_height("alice", 121), HandleMsg::ClaimRefund { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(handle(&mut deps, env_at_height("alice", 122), HandleMsg::ClaimRefund { pool: 1, round: 0, padding: None }).is_err());
+        assert!(handle(&mut deps, env_at_height("alice", 122), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+
+        // a new empty round was opened for the pool
+        let round = current_round(&deps, 1);
+        assert_eq!(round.round_number, 1);
+        assert_eq!(round.pool_size, Uint128(0));
+        assert!(!round.cancelled);
+    }
+
+    #[test]
+    fn test_permissionless_trigger() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+
+        assert!(handle(&mut deps, env_at_height("bot", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).is_err());
+        assert!(handle(&mut deps, mock_env("bot", &[]), HandleMsg::ChangeTriggerMode { trigger_mode: TriggerMode::Permissionless }).is_err());
+        handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangeTriggerMode { trigger_mode: TriggerMode::Permissionless }).unwrap();
+
+        // the first commitment of the round wins
+        handle(&mut deps, env_at_height("bot", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools.is_empty()));
+
+        // only the committer can reveal, and gets the triggerer fee
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "bot".to_string() }).unwrap();
+        assert!(res.messages.is_empty());
+        let res = handle(&mut deps, env_at_height("bot", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "bot".to_string() }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(current_round(&deps, 1).round_number, 1);
+
+        // the round can't be drawn again in the same block
+        let res = handle(&mut deps, env_at_height("bot", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "bot".to_string() }).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(current_round(&deps, 1).round_number, 1);
+    }
+
+    #[test]
+    fn test_carried_jackpot_needs_tickets() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 2, 5).unwrap();
+        handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangeTriggerMode { trigger_mode: TriggerMode::Permissionless }).unwrap();
+
+        // the carried jackpot covers min_entries entry fees but nobody bet on the round
+        {
+            let mut pool_rounds = PrefixedStorage::multilevel(&[ROUNDS_STATE, &1u32.to_be_bytes()], &mut deps.storage);
+            let mut pool_rounds_store = AppendStoreMut::<RoundStruct, _>::attach(&mut pool_rounds).unwrap().unwrap();
+            let mut round = pool_rounds_store.get_at(0).unwrap();
+            round.pool_size = Uint128(5000);
+            round.carried_pool_size = Uint128(5000);
+            pool_rounds_store.set_at(0, &round).unwrap();
+        }
+        let res = handle(&mut deps, env_at_height("bot", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools.is_empty()));
+        assert!(current_round(&deps, 1).committer.is_none());
+
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 2).unwrap();
+        let res = handle(&mut deps, env_at_height("bot", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::CommitEntropy { pools, .. } if pools == vec![1]));
+    }
+
+    #[test]
+    fn test_missed_reveal() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 5).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangeTriggerMode { trigger_mode: TriggerMode::Permissionless }).unwrap();
+        let committed_pools = |res: HandleResponse| match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::CommitEntropy { pools, .. } => pools,
+            _ => panic!("unexpected"),
         };
 
-        let rounds_iter = tier3_rounds_store
-        .iter()
-        .rev()
-        .skip((page * page_size) as _)
-        .take(page_size as _)
-        .map(|x| {
-            let mut modified = x.unwrap();
-            modified.users_picked_numbers_count = vec![];
-            return modified
-        });
+        // the bot never reveals, after the reveal timeout neither it nor anyone else but the triggerer can commit again
+        assert_eq!(committed_pools(handle(&mut deps, env_at_height("bot", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap()), vec![1]);
+        assert_eq!(committed_pools(handle(&mut deps, env_at_height("bot", 111), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap()), Vec::<u32>::new());
+        assert_eq!(committed_pools(handle(&mut deps, env_at_height("other_bot", 111), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("bot") }).unwrap()), Vec::<u32>::new());
+        assert_eq!(committed_pools(handle(&mut deps, env_at_height("triggerer", 111), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap()), vec![1]);
+        assert_eq!(current_round(&deps, 1).missed_reveals, vec![HumanAddr::from("bot")]);
 
-        let rounds: Vec<RoundStruct> = rounds_iter.collect();
-        tier3_rounds = Some(rounds);
+        handle(&mut deps, env_at_height("triggerer", 112), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        assert_eq!(current_round(&deps, 1).round_number, 1);
     }
-    
-    to_binary(&QueryAnswer::GetPaginatedRounds {
-        tier1_rounds,
-        tier2_rounds,
-        tier3_rounds
-    })
-}
 
-fn query_rounds <S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    tier1_rounds: Vec<u32>,
-    tier2_rounds: Vec<u32>,
-    tier3_rounds: Vec<u32>,
-) -> StdResult<Binary> {
-    let mut rounds : Vec<RoundStruct> = vec![];
+    #[test]
+    fn test_trigger_aggregates_fees() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 1).unwrap();
+        create_pool_helper(&mut deps, 500, 1, 2).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "alice", 500, 2, 1).unwrap();
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2], entropy: "secret".to_string() }).unwrap();
+
+        // both triggerer fees are sent in a single transfer
+        assert_eq!(res.messages.len(), 1);
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!(pools.len(), 2);
+                assert_eq!(pools[0].pool, 1);
+                assert_eq!(pools[0].lucky_numbers, vec![1]);
+                assert_eq!(pools[0].winner_tickets_count, 1);
+                assert_eq!(pools[0].prize_pool_size, Uint128(500));
+                assert_eq!(pools[1].triggerer_fee, Uint128(250));
+                assert_eq!(pools[1].next_round_pool_size + pools[1].prize_pool_size, Uint128(250));
+            }
+            _ => panic!("unexpected handle answer"),
+        }
+    }
 
-    let tier1_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &deps.storage);
-    let tier1_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier1_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetRounds {
-            rounds
-        }) 
-    };
+    #[test]
+    fn test_auto_distribute_prizes() {
+        let mut deps = init_helper();
+        for max_auto_winners in [2, 1] {
+            create_pool_with(&mut deps, TierConfig {
+                auto_distribute: true,
+                max_auto_winners,
+                ..tier_config_helper(1000, 2, 1)
+            }).unwrap();
+        }
+        for pool in [1, 2] {
+            bet_helper(&mut deps, "alice", 1000, pool, 1).unwrap();
+            bet_helper(&mut deps, "bob", 1000, pool, 1).unwrap();
+        }
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2], entropy: "secret".to_string() }).unwrap();
+
+        // the triggerer fees and one batch with the pool 1 winners
+        assert_eq!(res.messages.len(), 2);
+        match &res.messages[1] {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                let batch: Snip20Msg = from_binary(msg).unwrap();
+                assert_eq!(batch, Snip20Msg::batch_transfer(vec![
+                    (HumanAddr::from("alice"), Uint128(750)),
+                    (HumanAddr::from("bob"), Uint128(750)),
+                ]));
+            }
+            _ => panic!("unexpected message"),
+        }
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::TriggerLuckyNumber { pools } if pools[0].prizes_distributed && !pools[1].prizes_distributed));
 
-    for round_number in tier1_rounds {
-        let mut round_state: RoundStruct = tier1_rounds_store.get_at(round_number).unwrap();
-        round_state.users_picked_numbers_count = vec![];
-        rounds.push(round_state)
+        // pool 1 prizes were already paid, pool 2 had more winners than the cap
+        assert!(handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 2, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages.len(), 1);
     }
-    
-    let tier2_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &deps.storage);
-    let tier2_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier2_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetRounds {
-            rounds
-        }) 
-    };
 
-    for round_number in tier2_rounds {
-        let mut round_state: RoundStruct = tier2_rounds_store.get_at(round_number).unwrap();
-        round_state.users_picked_numbers_count = vec![];
-        rounds.push(round_state)
+    #[test]
+    fn test_claim_all() {
+        let mut deps = init_helper();
+        for pool in [1, 2, 3] {
+            create_pool_helper(&mut deps, 1000, 1, 1).unwrap();
+            bet_helper(&mut deps, "alice", 1000, pool, 1).unwrap();
+        }
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2, 3], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2, 3], entropy: "secret".to_string() }).unwrap();
+
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::ClaimAll { start: None, limit: Some(2), padding: None }).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::ClaimAll { claimed_bets: 2, amount: Uint128(1000), next_start: Some(2) }));
+
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::ClaimAll { start: Some(2), limit: None, padding: None }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::ClaimAll { claimed_bets: 1, amount: Uint128(500), next_start: None }));
+
+        // everything was claimed already
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::ClaimAll { start: None, limit: None, padding: None }).unwrap();
+        assert!(res.messages.is_empty());
+        assert!(handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+        assert!(handle(&mut deps, env_at_height("bob", 102), HandleMsg::ClaimAll { start: None, limit: None, padding: None }).is_err());
+
+        // starting past the last bet is an empty page
+        for start in [3, u32::MAX] {
+            let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::ClaimAll { start: Some(start), limit: Some(u32::MAX), padding: None }).unwrap();
+            let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+            assert!(matches!(answer, HandleAnswer::ClaimAll { claimed_bets: 0, amount: Uint128(0), next_start: None }));
+        }
     }
 
-    let tier3_rounds_storage = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &deps.storage);
-    let tier3_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier3_rounds_storage) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::GetRounds {
-            rounds
-        }) 
-    };
+    #[test]
+    fn test_pagination_bounds() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        handle(&mut deps, mock_env("alice", &[]), HandleMsg::SetViewingKey { key: "alice_key".to_string(), padding: None }).unwrap();
+
+        for (page, page_size) in [(u32::MAX, u32::MAX), (u32::MAX, 1), (1, u32::MAX)] {
+            let res = query(&deps, QueryMsg::GetPaginatedUserBets { user_address: HumanAddr::from("alice"), viewing_key: "alice_key".to_string(), page, page_size }).unwrap();
+            match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+                QueryAnswer::GetPaginatedUserBets { user_bets, user_bets_total_count, .. } => assert_eq!((user_bets.len(), user_bets_total_count), (0, 1)),
+                _ => panic!("unexpected query answer"),
+            }
+            let res = query(&deps, QueryMsg::GetPaginatedRounds { pools: vec![1], page, page_size }).unwrap();
+            match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+                QueryAnswer::GetPaginatedRounds { rounds } => assert!(rounds.is_empty()),
+                _ => panic!("unexpected query answer"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_user_bets_storage() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 1).unwrap();
+
+        // a withdrawn bet stays listed but frees the round ticket
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 1).unwrap();
+
+        let (user_bets, total_count) = paginated_user_bets(&mut deps, "alice");
+        assert_eq!(total_count, 2);
+        assert_eq!((user_bets[0].ticket, user_bets[0].withdrawn), (1, false));
+        assert_eq!((user_bets[1].ticket, user_bets[1].withdrawn), (0, true));
+
+        let res = query(&deps, QueryMsg::GetUserBets { user_address: HumanAddr::from("alice"), viewing_key: "alice_key".to_string(), keys: vec![bet_mapping_key(1, 0, 1), "unknown".to_string()] }).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::GetUserBets { user_bets } => assert_eq!(user_bets.len(), 1),
+            _ => panic!("unexpected query answer"),
+        }
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
 
-    for round_number in tier3_rounds {
-        let mut round_state: RoundStruct = tier3_rounds_store.get_at(round_number).unwrap();
-        round_state.users_picked_numbers_count = vec![];
-        rounds.push(round_state)
+        // the withdrawn ticket picked the lucky number too but is not paid
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::ClaimAll { start: None, limit: None, padding: None }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::ClaimAll { claimed_bets: 1, amount: Uint128(750), next_start: None }));
     }
-    
-    to_binary(&QueryAnswer::GetRounds {
-        rounds
-    })
-}
 
-fn query_tier_configs<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    tier1: bool,
-    tier2: bool,
-    tier3: bool
-) -> StdResult<Binary> {
+    #[test]
+    fn test_event_logs() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 5).unwrap();
+        let logs = |res: &HandleResponse| -> Vec<(String, String)> { res.log.iter().map(|attr| (attr.key.clone(), attr.value.clone())).collect() };
+
+        // the picked number is not logged before the draw
+        let res = bet_helper(&mut deps, "alice", 1000, 1, 4).unwrap();
+        assert_eq!(logs(&res), vec![
+            ("action".to_string(), "bet".to_string()),
+            ("pool".to_string(), "1".to_string()),
+            ("round".to_string(), "0".to_string()),
+            ("ticket".to_string(), "0".to_string()),
+            ("amount".to_string(), "1000".to_string()),
+        ]);
+        assert!(!res.log.iter().any(|attr| attr.value.contains('4')));
+
+        let res = handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(logs(&res), vec![
+            ("action".to_string(), "withdrawl".to_string()),
+            ("pool".to_string(), "1".to_string()),
+            ("round".to_string(), "0".to_string()),
+            ("tickets".to_string(), "1".to_string()),
+            ("amount".to_string(), "1000".to_string()),
+        ]);
+
+        bet_helper(&mut deps, "alice", 1000, 1, 5).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        assert_eq!(logs(&res)[1], ("pools".to_string(), "1".to_string()));
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        assert_eq!(load_round(&deps.storage, 1, 0).unwrap().lucky_number, Some(5));
+        assert_eq!(logs(&res), vec![
+            ("action".to_string(), "trigger_lucky_number".to_string()),
+            ("pool".to_string(), "1".to_string()),
+            ("round".to_string(), "0".to_string()),
+            ("winner_tickets".to_string(), "1".to_string()),
+            ("prize_pool_size".to_string(), "500".to_string()),
+            ("lucky_number".to_string(), "5".to_string()),
+            ("winning_ticket".to_string(), "none".to_string()),
+            ("triggerer_fee".to_string(), "500".to_string()),
+            ("house_fee".to_string(), "0".to_string()),
+        ]);
+
+        // claiming the prize logs the same attributes as withdrawing the bet
+        let res = handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(logs(&res), vec![
+            ("action".to_string(), "withdrawl".to_string()),
+            ("pool".to_string(), "1".to_string()),
+            ("round".to_string(), "0".to_string()),
+            ("tickets".to_string(), "1".to_string()),
+            ("amount".to_string(), "500".to_string()),
+        ]);
+    }
 
-    let mut tier1_configs:Option<TierConfig> = None;
-    let mut tier2_configs:Option<TierConfig> = None;
-    let mut tier3_configs:Option<TierConfig> = None;
+    #[test]
+    fn test_contract_errors() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 2).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 2).unwrap();
+
+        assert_contract_error(handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 9, round: 0, padding: None }), ContractError::InvalidPool { pool: 9 });
+        assert_contract_error(query(&deps, QueryMsg::GetTierConfigs { pools: vec![9] }), ContractError::InvalidPool { pool: 9 });
+        assert_contract_error(handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 1, round: 5, padding: None }), ContractError::UnknownRound { pool: 1, round: 5 });
+        assert_contract_error(query(&deps, QueryMsg::GetRounds { rounds: vec![PoolRound { pool: 1, round: 5 }] }), ContractError::UnknownRound { pool: 1, round: 5 });
+        assert_contract_error(handle(&mut deps, mock_env("carol", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }), ContractError::NoBets { pool: 1, round: 0 });
+        assert_contract_error(handle(&mut deps, mock_env("alice", &[]), HandleMsg::ChangeAdmin { admin: HumanAddr::from("alice") }), ContractError::Unauthorized { action: "change owner".to_string() });
+        assert_contract_error(handle(&mut deps, env_at_height("alice", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }), ContractError::Unauthorized { action: "trigger the draws".to_string() });
+        assert_contract_error(query(&deps, QueryMsg::GetUserBets { user_address: HumanAddr::from("alice"), viewing_key: "wrong_key".to_string(), keys: vec![] }), ContractError::InvalidViewingKey);
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let (winner, loser) = match load_round(&deps.storage, 1, 0).unwrap().lucky_number {
+            Some(1) => ("alice", "bob"),
+            _ => ("bob", "alice"),
+        };
+        assert_contract_error(handle(&mut deps, env_at_height(loser, 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }), ContractError::NotAWinner { pool: 1, round: 0 });
+        handle(&mut deps, env_at_height(winner, 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_contract_error(handle(&mut deps, env_at_height(winner, 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }), ContractError::AlreadyClaimed { pool: 1, round: 0 });
 
-    if tier1 {
-        let tier_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_1, &deps.storage);
-        let entry_fee: Uint128 = load(&tier_config, b"entry_fee")?;
-        let triggerer_fee: Uint128 = load(&tier_config, b"triggerer_fee")?;
-        let min_entries: i16 = load(&tier_config, b"min_entries")?;
-        let max_rand_number: i16 = load(&tier_config, b"max_rand_number")?;
+        // storage errors keep their kind through the conversion
+        assert!(matches!(StdError::from(ContractError::from(StdError::not_found("RoundStruct"))), StdError::NotFound { .. }));
+    }
 
-        tier1_configs = Some(TierConfig {
-            entry_fee,
-            triggerer_fee,
-            min_entries,
-            max_rand_number
-        });
+    // permits signed for the mock contract by the secp256k1 key [7u8; 32]
+    const PERMIT_SIGNER: &str = "secret150rtrmj2f8vl9tem8qpfw36ylw5jg9j23x7qy9";
+
+    fn permit_helper(contract: &str, chain_id: &str, permission: &str, signature: &str) -> Permit {
+        from_binary(&Binary::from(format!(r#"{{
+            "params": {{ "allowed_tokens": ["{}"], "permit_name": "lucky", "chain_id": "{}", "permissions": ["{}"] }},
+            "signature": {{
+                "pub_key": {{ "type": "tendermint/PubKeySecp256k1", "value": "ApicC3bLVjlx/cm+8x7AbDVg8ySdbunl2DxXYlWW4F9v" }},
+                "signature": "{}"
+            }}
+        }}"#, contract, chain_id, permission, signature).as_bytes())).unwrap()
     }
 
-    if tier2 {
-        let tier_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_2, &deps.storage);
-        let entry_fee: Uint128 = load(&tier_config, b"entry_fee")?;
-        let triggerer_fee: Uint128 = load(&tier_config, b"triggerer_fee")?;
-        let min_entries: i16 = load(&tier_config, b"min_entries")?;
-        let max_rand_number: i16 = load(&tier_config, b"max_rand_number")?;
+    #[test]
+    fn test_query_permits() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 2).unwrap();
+        bet_helper(&mut deps, PERMIT_SIGNER, 1000, 1, 2).unwrap();
+
+        let permit = permit_helper("cosmos2contract", "cosmos-testnet-14002", "owner", "dC45ynHulRXwbE6rs/B6CKTe3EzdM4+3MtzQYs38jnhitqZU++L8Yh3H24TBIjZSySLZwubn0fB76Who2yj1jQ==");
+        let with_permit = |deps: &Extern<MockStorage, MockApi, MockQuerier>, permit: &Permit, query_msg: QueryWithPermit| query(deps, QueryMsg::WithPermit { permit: permit.clone(), query: query_msg });
+
+        // same answers as the viewing key queries
+        handle(&mut deps, mock_env(PERMIT_SIGNER, &[]), HandleMsg::SetViewingKey { key: "key".to_string(), padding: None }).unwrap();
+        assert_eq!(
+            with_permit(&deps, &permit, QueryWithPermit::GetPaginatedUserBets { page: 0, page_size: 10 }).unwrap(),
+            query(&deps, QueryMsg::GetPaginatedUserBets { user_address: HumanAddr::from(PERMIT_SIGNER), viewing_key: "key".to_string(), page: 0, page_size: 10 }).unwrap()
+        );
+        let res = with_permit(&deps, &permit, QueryWithPermit::GetUserBets { keys: vec![bet_mapping_key(1, 0, 0)] }).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::GetUserBets { user_bets } => assert_eq!(user_bets[0].numbers, vec![2]),
+            _ => panic!("unexpected query answer"),
+        }
+        let res = with_permit(&deps, &permit, QueryWithPermit::GetDeposit { pool: 1 }).unwrap();
+        match from_binary(&from_binary::<Binary>(&res).unwrap()).unwrap() {
+            QueryAnswer::GetDeposit { deposit, .. } => assert_eq!(deposit, Uint128(0)),
+            _ => panic!("unexpected query answer"),
+        }
 
-        tier2_configs = Some(TierConfig {
-            entry_fee,
-            triggerer_fee,
-            min_entries,
-            max_rand_number
-        });
+        let balance_permit = permit_helper("cosmos2contract", "cosmos-testnet-14002", "balance", "+U9ciOtS/Sr8hR0MprX4qH5UupAdCSGVgiaGxOMOqwxfm6oSSYhsmaPXcd9WH7PjH8QgMqx22bU2PjWSmYWP+w==");
+        assert_contract_error(with_permit(&deps, &balance_permit, QueryWithPermit::GetDeposit { pool: 1 }), ContractError::MissingPermitPermission);
+        let other_contract_permit = permit_helper("other_contract", "cosmos-testnet-14002", "owner", "iQGV9SUU+2xBgMWGTtqALj9he7crFjwc1JbJQkUNcmAD3IWhjYVTJO/FHS+1KOvUpIAvs6+373+GXhezYDXjzA==");
+        assert_contract_error(with_permit(&deps, &other_contract_permit, QueryWithPermit::GetDeposit { pool: 1 }), ContractError::InvalidPermit { reason: "permit is not for this contract".to_string() });
+        let other_chain_permit = permit_helper("cosmos2contract", "secret-4", "owner", "ewk2OpIdklWPHT08y/ZMltYwmR0HTuIw2c3dU/tUvY9p71kEiyzqdgE6VUtND/k2iisa2XOKIgyVfBKnrm7sCQ==");
+        assert_contract_error(with_permit(&deps, &other_chain_permit, QueryWithPermit::GetDeposit { pool: 1 }), ContractError::InvalidPermit { reason: "permit is not for this chain".to_string() });
+        let mut tampered_permit = permit.clone();
+        tampered_permit.params.permit_name = "tampered".to_string();
+        assert_contract_error(with_permit(&deps, &tampered_permit, QueryWithPermit::GetDeposit { pool: 1 }), ContractError::InvalidPermit { reason: "signature does not match the permit".to_string() });
+
+        // only the signer revokes its permits
+        handle(&mut deps, mock_env("alice", &[]), HandleMsg::RevokePermit { permit_name: "lucky".to_string(), padding: None }).unwrap();
+        assert!(with_permit(&deps, &permit, QueryWithPermit::GetDeposit { pool: 1 }).is_ok());
+        let res = handle(&mut deps, mock_env(PERMIT_SIGNER, &[]), HandleMsg::RevokePermit { permit_name: "lucky".to_string(), padding: None }).unwrap();
+        assert_eq!(res.log[0].value, "revoke_permit");
+        assert_contract_error(with_permit(&deps, &permit, QueryWithPermit::GetDeposit { pool: 1 }), ContractError::InvalidPermit { reason: "permit lucky was revoked".to_string() });
     }
 
-    if tier3 {
-        let tier_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_3, &deps.storage);
-        let entry_fee: Uint128 = load(&tier_config, b"entry_fee")?;
-        let triggerer_fee: Uint128 = load(&tier_config, b"triggerer_fee")?;
-        let min_entries: i16 = load(&tier_config, b"min_entries")?;
-        let max_rand_number: i16 = load(&tier_config, b"max_rand_number")?;
+    #[test]
+    fn test_response_padding() {
+        let mut deps = init_helper();
+        create_pool_helper(&mut deps, 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        let data_len = |res: HandleResponse| res.data.unwrap().len();
+
+        let key_len = data_len(handle(&mut deps, mock_env("alice", &[]), HandleMsg::CreateViewingKey { entropy: "alice".to_string(), padding: None }).unwrap());
+        let refund_len = data_len(handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap());
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let win_len = data_len(handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap());
+        assert_eq!(key_len % BLOCK_SIZE, 0);
+        assert_eq!(refund_len, BLOCK_SIZE);
+        assert_eq!(win_len, refund_len);
+
+        // padded answers still parse
+        let res = query(&deps, QueryMsg::GetPools {}).unwrap();
+        assert_eq!(res.len() % BLOCK_SIZE, 0);
+        assert!(from_binary::<QueryAnswer>(&from_binary::<Binary>(&res).unwrap()).is_ok());
+    }
 
-        tier3_configs = Some(TierConfig {
-            entry_fee,
-            triggerer_fee,
-            min_entries,
-            max_rand_number
+    #[test]
+    fn test_multiple_tickets() {
+        let mut deps = init_helper();
+        for _ in 0..2 {
+            create_pool_with(&mut deps, TierConfig {
+                max_tickets_per_user: 2,
+                ..tier_config_helper(1000, 2, 1)
+            }).unwrap();
+        }
+
+        let res = bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        assert!(matches!(from_binary(&res.data.unwrap()).unwrap(), HandleAnswer::Bet { pool: 1, round: 0, ticket: 0 }));
+        let res = bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        assert!(matches!(from_binary(&res.data.unwrap()).unwrap(), HandleAnswer::Bet { pool: 1, round: 0, ticket: 1 }));
+        assert!(bet_helper(&mut deps, "alice", 1000, 1, 1).is_err());
+        bet_helper(&mut deps, "bob", 1000, 1, 1).unwrap();
+        let round = current_round(&deps, 1);
+        assert_eq!((round.users_count, round.tickets_count, round.users_picked_numbers_count[0]), (2, 3, 3));
+
+        // withdrawing an unfinished round refunds every ticket
+        bet_helper(&mut deps, "alice", 1000, 2, 1).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 2, 1).unwrap();
+        let res = handle(&mut deps, mock_env("alice", &[]), HandleMsg::Withdrawl { pool: 2, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("alice"), Uint128(2000), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        assert_eq!(current_round(&deps, 2).tickets_count, 0);
+
+        // the prize is split by winning tickets
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("alice"), Uint128(1666), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        assert!(handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+    }
+
+    #[test]
+    fn test_lotto_brackets() {
+        let mut deps = init_helper();
+        let lotto_pool = |picks: u8, brackets: Vec<PrizeBracket>| create_pool_msg(TierConfig {
+            pool_type: PoolType::Lotto { picks, brackets },
+            ..tier_config_helper(1000, 2, 2)
         });
+        assert!(handle(&mut deps, mock_env("admin", &[]), lotto_pool(3, vec![])).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), lotto_pool(2, vec![])).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), lotto_pool(2, vec![PrizeBracket { matches: 3, share_bps: 100 }])).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), lotto_pool(2, vec![PrizeBracket { matches: 2, share_bps: 6000 }, PrizeBracket { matches: 1, share_bps: 5000 }])).is_err());
+        handle(&mut deps, mock_env("admin", &[]), lotto_pool(2, vec![PrizeBracket { matches: 2, share_bps: 5000 }, PrizeBracket { matches: 1, share_bps: 3000 }])).unwrap();
+
+        let bet = |numbers: Vec<i16>, user: &str| HandleMsg::Receive {
+            sender: HumanAddr::from(user),
+            from: HumanAddr::from(user),
+            amount: Uint128(1000),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers, entropy: None, padding: None }).unwrap(),
+        };
+        assert!(handle(&mut deps, mock_env("token", &[]), bet(vec![1], "alice")).is_err());
+        assert!(handle(&mut deps, mock_env("token", &[]), bet(vec![1, 1], "alice")).is_err());
+        assert!(handle(&mut deps, mock_env("token", &[]), bet(vec![1, 3], "alice")).is_err());
+        handle(&mut deps, mock_env("token", &[]), bet(vec![1, 2], "alice")).unwrap();
+        handle(&mut deps, mock_env("token", &[]), bet(vec![2, 1], "bob")).unwrap();
+        handle(&mut deps, mock_env("token", &[]), bet(vec![1, 2], "carol")).unwrap();
+        // withdrawn tickets are left out of the brackets
+        handle(&mut deps, mock_env("carol", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+
+        // both numbers are drawn so every ticket matches the two of them
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!(pools[0].lucky_numbers.len(), 2);
+                assert_eq!(pools[0].winner_tickets_count, 2);
+                assert_eq!(pools[0].prize_pool_size, Uint128(750));
+                assert_eq!(pools[0].next_round_pool_size, Uint128(750));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &1u32.to_be_bytes()], &deps.storage);
+        let round: RoundStruct = AppendStore::<RoundStruct, _>::attach(&pool_rounds).unwrap().unwrap().get_at(0).unwrap();
+        assert_eq!(round.brackets[0].winners, 2);
+        assert_eq!(round.brackets[0].prize_per_ticket, Uint128(375));
+        assert_eq!(round.brackets[1].winners, 0);
+
+        let res = handle(&mut deps, env_at_height("bob", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("bob"), Uint128(375), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        let query = from_binary::<Binary>(&query(&deps, QueryMsg::VerifyRound { pool: 1, round: 0 }).unwrap()).unwrap();
+        assert!(matches!(from_binary(&query).unwrap(), QueryAnswer::VerifyRound { valid: true, .. }));
     }
-    
-    to_binary(&QueryAnswer::GetTierConfigs {
-        tier1_configs,
-        tier2_configs,
-        tier3_configs
-    })
-}
 
-fn query_check_triggers<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let mut tier1_trigger: bool = false;
-    let mut tier2_trigger: bool = false;
-    let mut tier3_trigger: bool = false;
-
-    let tier1_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_1, &deps.storage);
-    let min_entries_tier1: i16 = load(&tier1_config, b"min_entries")?;
-    let tier1_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier1".to_string().as_bytes()], &deps.storage);
-    let tier1_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier1_rounds) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::CheckTriggers {
-            tier1_trigger,
-            tier2_trigger,
-            tier3_trigger
-        })
-    };
-        
-    let tier1_cur_round: RoundStruct = tier1_rounds_store.get_at(tier1_rounds_store.len() - 1).unwrap();
+    #[test]
+    fn test_raffle() {
+        let mut deps = init_helper();
+        create_pool_with(&mut deps, TierConfig {
+            pool_type: PoolType::Raffle,
+            ..tier_config_helper(1000, 2, 1)
+        }).unwrap();
+
+        assert!(bet_helper(&mut deps, "alice", 1000, 1, 1).is_err());
+        for user in ["alice", "bob", "carol"] {
+            handle(&mut deps, mock_env("token", &[]), HandleMsg::Receive {
+                sender: HumanAddr::from(user),
+                from: HumanAddr::from(user),
+                amount: Uint128(1000),
+                msg: to_binary(&HandleMsg::Bet { pool: 1, numbers: vec![], entropy: None, padding: None }).unwrap(),
+            }).unwrap();
+        }
+        handle(&mut deps, mock_env("carol", &[]), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+
+        // the round always has a single winner among the tickets left
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        let winning_ticket = match answer {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!(pools[0].winner_tickets_count, 1);
+                assert_eq!(pools[0].next_round_pool_size, Uint128(0));
+                pools[0].winning_ticket.unwrap()
+            }
+            _ => panic!("unexpected"),
+        };
+        assert!(winning_ticket < 2);
+
+        let (winner, loser) = if winning_ticket == 0 { ("alice", "bob") } else { ("bob", "alice") };
+        assert!(handle(&mut deps, env_at_height(loser, 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
+        let res = handle(&mut deps, env_at_height(winner, 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from(winner), Uint128(1500), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
 
-    if tier1_cur_round.users_count >= min_entries_tier1 as u32 {
-        tier1_trigger = true;
+        let query = from_binary::<Binary>(&query(&deps, QueryMsg::VerifyRound { pool: 1, round: 0 }).unwrap()).unwrap();
+        assert!(matches!(from_binary(&query).unwrap(), QueryAnswer::VerifyRound { valid: true, .. }));
     }
 
-    let tier2_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_2, &deps.storage);
-    let min_entries_tier2: i16 = load(&tier2_config, b"min_entries")?;
-    let tier2_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier2".to_string().as_bytes()], &deps.storage);
-    let tier2_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier2_rounds) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::CheckTriggers {
-            tier1_trigger,
-            tier2_trigger,
-            tier3_trigger
-        })
-    };
-        
-    let tier2_cur_round: RoundStruct = tier2_rounds_store.get_at(tier2_rounds_store.len() - 1).unwrap();
+    // yield source answering every SNIP-20 balance query with the accrued yield
+    struct YieldQuerier {
+        accrued_yield: u128
+    }
 
-    if tier2_cur_round.users_count >= min_entries_tier2 as u32 {
-        tier2_trigger = true;
+    impl Querier for YieldQuerier {
+        fn raw_query(&self, _bin_request: &[u8]) -> QuerierResult {
+            Ok(to_binary(&snip20::BalanceResponse { balance: snip20::Balance { amount: Uint128(self.accrued_yield) } }))
+        }
     }
 
-    let tier3_config = ReadonlyPrefixedStorage::new(LUCKY_NUMBER_CONFIG_TIER_3, &deps.storage);
-    let min_entries_tier3: i16 = load(&tier3_config, b"min_entries")?;
-    let tier3_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &"tier3".to_string().as_bytes()], &deps.storage);
-    let tier3_rounds_store = if let Some(result) = AppendStore::<RoundStruct, _>::attach(&tier3_rounds) {
-        result?
-    } else {
-        return to_binary(&QueryAnswer::CheckTriggers {
-            tier1_trigger,
-            tier2_trigger,
-            tier3_trigger
-        })
-    };
-        
-    let tier3_cur_round: RoundStruct = tier3_rounds_store.get_at(tier3_rounds_store.len() - 1).unwrap();
+    #[test]
+    fn test_no_loss_pool() {
+        let mut deps = init_helper().change_querier(|_| YieldQuerier { accrued_yield: 0 });
+        let create_pool = |yield_source: &str| create_pool_msg(TierConfig {
+            min_triggerer_fee: Some(Uint128(100)),
+            pool_type: PoolType::NoLoss { yield_source: HumanAddr::from(yield_source), yield_source_hash: "yield_hash".to_string() },
+            ..tier_config_helper(1000, 2, 1)
+        });
+        let res = handle(&mut deps, mock_env("admin", &[]), create_pool("yield")).unwrap();
+        assert_eq!(res.messages[0], snip20::set_viewing_key_msg("token_vk".to_string(), None, BLOCK_SIZE, "yield_hash".to_string(), HumanAddr::from("yield")).unwrap());
+
+        let deposit = |user: &str, amount: u128, numbers: Vec<i16>| HandleMsg::Receive {
+            sender: HumanAddr::from(user),
+            from: HumanAddr::from(user),
+            amount: Uint128(amount),
+            msg: to_binary(&HandleMsg::Bet { pool: 1, numbers, entropy: None, padding: None }).unwrap(),
+        };
+        assert!(handle(&mut deps, mock_env("token", &[]), deposit("alice", 1000, vec![1])).is_err());
+        assert!(handle(&mut deps, mock_env("token", &[]), deposit("alice", 999, vec![])).is_err());
+        handle(&mut deps, mock_env("token", &[]), deposit("alice", 2000, vec![])).unwrap();
+        let res = handle(&mut deps, mock_env("token", &[]), deposit("alice", 1000, vec![])).unwrap();
+        assert!(matches!(from_binary(&res.data.unwrap()).unwrap(), HandleAnswer::Deposit { pool: 1, deposit: Uint128(3000) }));
+        handle(&mut deps, mock_env("token", &[]), deposit("bob", 1000, vec![])).unwrap();
+
+        // principal can be withdrawn, the tickets follow the balance
+        assert!(handle(&mut deps, mock_env("alice", &[]), HandleMsg::WithdrawDeposit { pool: 1, amount: Uint128(3001), padding: None }).is_err());
+        let res = handle(&mut deps, mock_env("alice", &[]), HandleMsg::WithdrawDeposit { pool: 1, amount: Uint128(500), padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("alice"), Uint128(500), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &1u32.to_be_bytes()], &deps.storage);
+        let round: RoundStruct = AppendStore::<RoundStruct, _>::attach(&pool_rounds).unwrap().unwrap().get_at(0).unwrap();
+        assert_eq!((round.users_count, round.tickets_count), (2, 3));
+
+        // no deposits after the betting cutoff
+        assert!(handle(&mut deps, env_at_height("token", 55), deposit("carol", 1000, vec![])).is_err());
+
+        // only the accrued yield is drawn and paid by the yield source
+        deps.querier = YieldQuerier { accrued_yield: 1100 };
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let winning_ticket = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!((pools[0].winner_tickets_count, pools[0].prize_pool_size, pools[0].triggerer_fee), (1, Uint128(1000), Uint128(100)));
+                assert!(pools[0].prizes_distributed);
+                pools[0].winning_ticket.unwrap()
+            }
+            _ => panic!("unexpected"),
+        };
+        let winner = if winning_ticket < 2 { "alice" } else { "bob" };
+        assert_eq!(res.messages, vec![
+            transfer_msg(HumanAddr::from(winner), Uint128(1000), None, BLOCK_SIZE, "yield_hash".to_string(), HumanAddr::from("yield")).unwrap(),
+            transfer_msg(HumanAddr::from("triggerer"), Uint128(100), None, BLOCK_SIZE, "yield_hash".to_string(), HumanAddr::from("yield")).unwrap(),
+        ]);
+
+        // deposits stay on the next round and can still be withdrawn
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &1u32.to_be_bytes()], &deps.storage);
+        let next_round: RoundStruct = AppendStore::<RoundStruct, _>::attach(&pool_rounds).unwrap().unwrap().get_at(1).unwrap();
+        assert_eq!((next_round.users_count, next_round.tickets_count), (2, 3));
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::WithdrawDeposit { pool: 1, amount: Uint128(2500), padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("alice"), Uint128(2500), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+
+        // empty deposits leave the depositors walked by the draw
+        let bob = deps.api.canonical_address(&HumanAddr::from("bob")).unwrap();
+        assert_eq!(deposit_ticket_owner(&deps.storage, 1, Uint128(1000), 0).unwrap(), Some(bob));
+        assert_eq!(deposit_ticket_owner(&deps.storage, 1, Uint128(1000), 1).unwrap(), None);
+        let depositors = ReadonlyPrefixedStorage::multilevel(&[DEPOSITORS, &1u32.to_be_bytes()], &deps.storage);
+        assert_eq!(AppendStore::<CanonicalAddr, _>::attach(&depositors).unwrap().unwrap().len(), 1);
+
+        let query = from_binary::<Binary>(&query(&deps, QueryMsg::VerifyRound { pool: 1, round: 0 }).unwrap()).unwrap();
+        assert!(matches!(from_binary(&query).unwrap(), QueryAnswer::VerifyRound { valid: true, .. }));
+
+        // a yield source backs a single pool and holds no other funds of the contract
+        assert!(handle(&mut deps, mock_env("admin", &[]), create_pool("yield")).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), create_pool("token")).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), create_pool("cosmos2contract")).is_err());
+        handle(&mut deps, mock_env("admin", &[]), create_pool("other_yield")).unwrap();
+    }
+
+    #[test]
+    fn test_jackpot_cap() {
+        let mut deps = init_helper();
+        // every ticket is on 1 and the draws pick 2, the whole pool is carried over
+        for overflow in [JackpotOverflow::Treasury, JackpotOverflow::SecondaryPrize] {
+            create_pool_with(&mut deps, TierConfig {
+                pool_type: PoolType::Lotto { picks: 1, brackets: vec![PrizeBracket { matches: 1, share_bps: 10_000 }] },
+                jackpot: Some(JackpotConfig { max_carry_over: Uint128(1000), overflow }),
+                ..tier_config_helper(1000, 2, 2)
+            }).unwrap();
+        }
+        for pool in [1, 2] {
+            for user in ["alice", "bob", "carol"] {
+                bet_helper(&mut deps, user, 1000, pool, 1).unwrap();
+            }
+        }
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2], entropy: "secret".to_string() }).unwrap();
+        // the overflow accrues on the treasury like the house fees
+        match from_binary(&from_binary::<Binary>(&query(&deps, QueryMsg::GetTreasury {}).unwrap()).unwrap()).unwrap() {
+            QueryAnswer::GetTreasury { accrued, total_collected, .. } => assert_eq!((accrued, total_collected), (Uint128(1500), Uint128(1500))),
+            _ => panic!("unexpected"),
+        }
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!((pools[0].next_round_pool_size, pools[0].overflow_pool_size), (Uint128(1000), Uint128(1500)));
+                assert_eq!((pools[1].next_round_pool_size, pools[1].overflow_pool_size), (Uint128(1000), Uint128(1500)));
+            }
+            _ => panic!("unexpected"),
+        }
+
+        let pool_rounds = ReadonlyPrefixedStorage::multilevel(&[ROUNDS_STATE, &2u32.to_be_bytes()], &deps.storage);
+        let round: RoundStruct = AppendStore::<RoundStruct, _>::attach(&pool_rounds).unwrap().unwrap().get_at(0).unwrap();
+        assert_eq!(round.lucky_numbers, vec![2]);
+        assert_eq!((round.entries_pool_size, round.secondary_prize_per_ticket), (Uint128(3000), Uint128(500)));
+        assert_eq!(current_round(&deps, 2).carried_pool_size, Uint128(1000));
 
-    // check if there are enougth pool size (pool_size >= min_entries * entry_fee)
-    if tier3_cur_round.users_count >= min_entries_tier3 as u32 {
-        tier3_trigger = true;
+        // the secondary prize is redeemed by the tickets that did not win
+        let res = handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 2, round: 0, padding: None }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("alice"), Uint128(500), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        assert!(handle(&mut deps, env_at_height("alice", 102), HandleMsg::Withdrawl { pool: 1, round: 0, padding: None }).is_err());
     }
 
-    to_binary(&QueryAnswer::CheckTriggers {
-        tier1_trigger,
-        tier2_trigger,
-        tier3_trigger
-    })
-}
+    #[test]
+    fn test_house_fee_treasury() {
+        let mut deps = init_helper();
+        let house_pool = |house_fee_bps: u16| create_pool_msg(TierConfig {
+            house_fee_bps,
+            ..tier_config_helper(1000, 2, 1)
+        });
+        assert!(handle(&mut deps, mock_env("admin", &[]), house_pool(10_001)).is_err());
+        handle(&mut deps, mock_env("admin", &[]), house_pool(1000)).unwrap();
+        bet_helper(&mut deps, "alice", 1000, 1, 1).unwrap();
+        bet_helper(&mut deps, "bob", 1000, 1, 1).unwrap();
+
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1], entropy: "secret".to_string() }).unwrap();
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        assert!(matches!(answer, HandleAnswer::TriggerLuckyNumber { pools } if pools[0].house_fee == Uint128(150) && pools[0].prize_pool_size == Uint128(1350)));
+
+        let treasury = |deps: &Extern<MockStorage, MockApi, MockQuerier>| match from_binary(&from_binary::<Binary>(&query(deps, QueryMsg::GetTreasury {}).unwrap()).unwrap()).unwrap() {
+            QueryAnswer::GetTreasury { treasury, accrued, total_collected, total_withdrawn } => (treasury, accrued, total_collected, total_withdrawn),
+            _ => panic!("unexpected"),
+        };
+        assert_eq!(treasury(&deps), (None, Uint128(150), Uint128(150), Uint128(0)));
+
+        // house fees accrue until the treasury withdraws them
+        assert!(handle(&mut deps, mock_env("admin", &[]), HandleMsg::WithdrawTreasury {}).is_err());
+        assert!(handle(&mut deps, mock_env("bob", &[]), HandleMsg::ChangeTreasury { treasury: HumanAddr::from("bob") }).is_err());
+        handle(&mut deps, mock_env("admin", &[]), HandleMsg::ChangeTreasury { treasury: HumanAddr::from("treasury") }).unwrap();
+        assert!(handle(&mut deps, mock_env("bob", &[]), HandleMsg::WithdrawTreasury {}).is_err());
+        let res = handle(&mut deps, mock_env("treasury", &[]), HandleMsg::WithdrawTreasury {}).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("treasury"), Uint128(150), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        assert_eq!(treasury(&deps), (Some(HumanAddr::from("treasury")), Uint128(0), Uint128(150), Uint128(150)));
+        assert!(handle(&mut deps, mock_env("treasury", &[]), HandleMsg::WithdrawTreasury {}).is_err());
+    }
 
-fn is_key_valid<S: ReadonlyStorage>(
-    storage: &S,
-    address: &CanonicalAddr,
-    viewing_key: String,
-) -> StdResult<bool> {
-    // load the address' key
-    let read_key = ReadonlyPrefixedStorage::new(PREFIX_VIEW_KEY, storage);
-    let load_key: Option<[u8; VIEWING_KEY_SIZE]> = may_load(&read_key, address.as_slice())?;
-    let input_key = ViewingKey(viewing_key);
-    // if a key was set
-    if let Some(expected_key) = load_key {
-        // and it matches
-        if input_key.check_viewing_key(&expected_key) {
-            return Ok(true);
+    #[test]
+    fn test_triggerer_fee_bps() {
+        let mut deps = init_helper();
+        let fee_pool = |triggerer_fee_bps: u16, min_triggerer_fee: Option<Uint128>, max_triggerer_fee: Option<Uint128>| create_pool_msg(TierConfig {
+            triggerer_fee_bps,
+            min_triggerer_fee,
+            max_triggerer_fee,
+            ..tier_config_helper(1000, 2, 1)
+        });
+        assert!(handle(&mut deps, mock_env("admin", &[]), fee_pool(10_001, None, None)).is_err());
+        assert!(handle(&mut deps, mock_env("admin", &[]), fee_pool(1000, Some(Uint128(300)), Some(Uint128(50)))).is_err());
+        handle(&mut deps, mock_env("admin", &[]), fee_pool(1000, Some(Uint128(50)), Some(Uint128(300)))).unwrap();
+        // the min fee must be covered by the min entries
+        assert!(handle(&mut deps, mock_env("admin", &[]), fee_pool(0, Some(Uint128(2001)), None)).is_err());
+        handle(&mut deps, mock_env("admin", &[]), fee_pool(0, Some(Uint128(2000)), None)).unwrap();
+
+        assert!(handle(&mut deps, mock_env("admin", &[]), change_tier_msg(1, TierConfig {
+            triggerer_fee_bps: 1000,
+            min_triggerer_fee: Some(Uint128(300)),
+            max_triggerer_fee: Some(Uint128(50)),
+            ..tier_config_helper(1000, 2, 1)
+        })).is_err());
+
+        for pool in [1, 2] {
+            bet_helper(&mut deps, "alice", 1000, pool, 1).unwrap();
+            bet_helper(&mut deps, "bob", 1000, pool, 1).unwrap();
+        }
+        handle(&mut deps, env_at_height("triggerer", 100), HandleMsg::CommitEntropy { pools: vec![1, 2], entropy_commitment: commitment("secret") }).unwrap();
+        let res = handle(&mut deps, env_at_height("triggerer", 101), HandleMsg::TriggerLuckyNumber { pools: vec![1, 2], entropy: "secret".to_string() }).unwrap();
+        assert_eq!(res.messages[0], transfer_msg(HumanAddr::from("triggerer"), Uint128(2200), None, BLOCK_SIZE, "token_hash".to_string(), HumanAddr::from("token")).unwrap());
+        let answer: HandleAnswer = from_binary(&res.data.unwrap()).unwrap();
+        match answer {
+            HandleAnswer::TriggerLuckyNumber { pools } => {
+                assert_eq!((pools[0].triggerer_fee, pools[0].prize_pool_size), (Uint128(200), Uint128(1800)));
+                assert_eq!((pools[1].triggerer_fee, pools[1].prize_pool_size), (Uint128(2000), Uint128(0)));
+            }
+            _ => panic!("unexpected"),
         }
-    } else {
-        // Checking the key will take significant time. We don't want to exit immediately if it isn't set
-        // in a way which will allow to time the command and determine if a viewing key doesn't exist
-        input_key.check_viewing_key(&[0u8; VIEWING_KEY_SIZE]);
     }
-    Ok(false)
-}
\ No newline at end of file
+}
@@ -0,0 +1,53 @@
+use cosmwasm_std::StdError;
+use snafu::Snafu;
+
+pub type ContractResult<T> = Result<T, ContractError>;
+
+/// Errors of the contract handlers and queries, turned into `StdError` by the entry points
+#[derive(Snafu, Debug)]
+pub enum ContractError {
+    #[snafu(display("Pool {} does not exist!", pool))]
+    InvalidPool { pool: u32 },
+
+    #[snafu(display("Round {} of pool {} does not exist!", round, pool))]
+    UnknownRound { pool: u32, round: u32 },
+
+    #[snafu(display("User does not have any bet on round {} of pool {}!", round, pool))]
+    NoBets { pool: u32, round: u32 },
+
+    #[snafu(display("User does not have winning tickets on round {} of pool {}!", round, pool))]
+    NotAWinner { pool: u32, round: u32 },
+
+    #[snafu(display("User already claimed the tickets of round {} of pool {}!", round, pool))]
+    AlreadyClaimed { pool: u32, round: u32 },
+
+    #[snafu(display("User does not have permissions to {}!", action))]
+    Unauthorized { action: String },
+
+    #[snafu(display("User+VK not valid!"))]
+    InvalidViewingKey,
+
+    #[snafu(display("Permit is not valid: {}!", reason))]
+    InvalidPermit { reason: String },
+
+    #[snafu(display("Permit does not grant the owner permission!"))]
+    MissingPermitPermission,
+
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+impl From<ContractError> for StdError {
+    fn from(error: ContractError) -> Self {
+        match error {
+            ContractError::Std { source } => source,
+            error => StdError::generic_err(error.to_string()),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod contract;
+pub mod error;
 pub mod msg;
+pub mod permit;
 pub mod state;
 pub mod viewing_key;
 pub mod rand;
@@ -1,8 +1,10 @@
-use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use cosmwasm_std::{Binary, CosmosMsg, HumanAddr, StdResult, Uint128, WasmMsg, to_binary};
+use secret_toolkit::utils::space_pad;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::state::{RoundStruct, UserBetStruct, UserBetsStruct};
+use crate::permit::Permit;
+use crate::state::{RoundStruct, UserBetStruct};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
@@ -11,25 +13,31 @@ pub struct InitMsg {
     pub token_address: HumanAddr,
     pub token_hash: String,
     pub token_vk: String,
-    pub tier1_entry_fee: Uint128,
-    pub tier1_triggerer_fee: Uint128,
-    pub tier1_min_entries: i16,
-    pub tier1_max_rand_number: i16,
-    pub tier2_entry_fee: Uint128,
-    pub tier2_triggerer_fee: Uint128,
-    pub tier2_min_entries: i16,
-    pub tier2_max_rand_number: i16,
-    pub tier3_entry_fee: Uint128,
-    pub tier3_triggerer_fee: Uint128,
-    pub tier3_min_entries: i16,
-    pub tier3_max_rand_number: i16,
+    /// number of blocks the triggerer has to reveal a committed entropy before the round reopens, at most 100000
+    pub reveal_timeout: u64,
+    /// defaults to only the triggerer being able to draw the lucky numbers
+    pub trigger_mode: Option<TriggerMode>,
+    /// receives the house fees, they accrue on the contract until withdrawn
+    pub treasury: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerMode {
+    /// only the triggerer address commits and reveals the entropy
+    Triggerer,
+    /// anyone can commit and reveal the entropy of a ready pool and gets its triggerer fee
+    Permissionless,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
     Receive { sender: HumanAddr, from: HumanAddr, amount: Uint128, msg: Binary },
-    Bet {tier: i8, number: i16},
+    /// one number on lucky number pools and the pool picks on lotto pools,
+    /// entropy is optional, it is mixed with every other user entropy on the lucky number draws
+    /// raffle pools take no numbers
+    Bet {pool: u32, #[serde(default)] numbers: Vec<i16>, entropy: Option<String>, padding: Option<String>},
     CreateViewingKey {
         entropy: String,
         padding: Option<String>,
@@ -38,11 +46,37 @@ pub enum HandleMsg {
         key: String,
         padding: Option<String>,
     },
-    Withdrawl {tier: i8, round: u32 },
+    /// revokes the sender permits named permit_name, they can't authenticate queries anymore
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    /// withdraws every ticket of an unfinished round or redeems every winning ticket of a finished one
+    Withdrawl {pool: u32, round: u32, padding: Option<String> },
+    ClaimRefund {pool: u32, round: u32, padding: Option<String> },
+    /// withdraws principal from a no-loss pool deposit
+    WithdrawDeposit { pool: u32, amount: Uint128, padding: Option<String> },
+    /// redeems every winning bet, scanning at most limit bets starting at the start index of the user bets
+    ClaimAll { start: Option<u32>, limit: Option<u32>, padding: Option<String> },
     ChangeAdmin {admin: HumanAddr},
     ChangeTriggerer { triggerer: HumanAddr},
-    ChangeTier { tier: i8, entry_fee: Uint128, triggerer_fee: Uint128, min_entries: i16, max_rand_number: i16 },
-    TriggerLuckyNumber {tier1: bool, tier2: bool, tier3: bool, entropy: u64}
+    ChangeTriggerMode { trigger_mode: TriggerMode },
+    ChangeTreasury { treasury: HumanAddr },
+    /// sends the accrued house fees to the treasury, callable by the admin or the treasury
+    WithdrawTreasury {},
+    /// round_duration, betting_cutoff and round_expiry are in seconds, up to a year, bets stop betting_cutoff seconds before the round closes
+    /// and rounds without min_entries are cancelled round_expiry seconds after closing.
+    /// max_tickets_per_user limits the tickets each user can buy on a round.
+    /// With auto_distribute the winners are paid on the draw, unless there are more than max_auto_winners winning tickets.
+    /// house_fee_bps of the prize pool is taken for the treasury on every draw.
+    /// The triggerer gets triggerer_fee_bps of the prize pool, kept within the optional min and max fees
+    CreatePool { name: String, description: String, display_order: u32, entry_fee: Uint128, triggerer_fee_bps: u16, min_triggerer_fee: Option<Uint128>, max_triggerer_fee: Option<Uint128>, min_entries: i16, max_rand_number: i16, round_duration: u64, betting_cutoff: u64, round_expiry: u64, auto_distribute: bool, max_auto_winners: u32, max_tickets_per_user: u32, pool_type: PoolType, jackpot: Option<JackpotConfig>, house_fee_bps: u16 },
+    ChangePoolMetadata { pool: u32, name: String, description: String, display_order: u32 },
+    ChangeTier { pool: u32, entry_fee: Uint128, triggerer_fee_bps: u16, min_triggerer_fee: Option<Uint128>, max_triggerer_fee: Option<Uint128>, min_entries: i16, max_rand_number: i16, round_duration: u64, betting_cutoff: u64, round_expiry: u64, auto_distribute: bool, max_auto_winners: u32, max_tickets_per_user: u32, pool_type: PoolType, jackpot: Option<JackpotConfig>, house_fee_bps: u16 },
+    /// closes the pools current rounds for betting, entropy_commitment is the sha256 of the entropy revealed later
+    /// once a commitment expires without its reveal, only the triggerer can commit to that round
+    CommitEntropy { pools: Vec<u32>, entropy_commitment: Binary },
+    TriggerLuckyNumber {pools: Vec<u32>, entropy: String}
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -52,10 +86,32 @@ pub enum QueryMsg {
     GetTriggerer {},
     GetUserBets {user_address: HumanAddr, viewing_key: String, keys: Vec<String>},
     GetPaginatedUserBets {user_address: HumanAddr, viewing_key: String, page: u32, page_size: u32},
-    GetPaginatedRounds { tier1: bool, tier2: bool, tier3: bool, page: u32, page_size: u32},
-    GetRounds { tier1_rounds: Vec<u32>, tier2_rounds: Vec<u32>, tier3_rounds: Vec<u32>},
-    GetTierConfigs { tier1: bool, tier2: bool, tier3: bool},
-    CheckTriggers {}
+    GetPaginatedRounds { pools: Vec<u32>, page: u32, page_size: u32},
+    GetRounds { rounds: Vec<PoolRound>},
+    GetTierConfigs { pools: Vec<u32>},
+    GetPools {},
+    /// queries can't read the block time, the client compares the returned times with its clock
+    CheckTriggers {},
+    /// Recompute the lucky number of a finished round from its stored seed
+    VerifyRound { pool: u32, round: u32 },
+    GetDeposit { pool: u32, user_address: HumanAddr, viewing_key: String },
+    GetTreasury {},
+    /// user queries authenticated by a SNIP-24 permit signed for this contract with the owner permission instead of a viewing key
+    WithPermit { permit: Permit, query: QueryWithPermit }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    GetUserBets { keys: Vec<String> },
+    GetPaginatedUserBets { page: u32, page_size: u32 },
+    GetDeposit { pool: u32 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolRound {
+    pub pool: u32,
+    pub round: u32,
 }
 
 // We define a custom struct for each query response
@@ -76,29 +132,59 @@ pub enum Snip20Msg {
         amount: Uint128,
         padding: Option<String>,
     },
+    BatchTransfer {
+        actions: Vec<TransferAction>,
+        padding: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferAction {
+    pub recipient: HumanAddr,
+    pub amount: Uint128,
+    pub memo: Option<String>,
 }
 
 impl Snip20Msg {
     pub fn register_receive(code_hash: String) -> Self {
         Snip20Msg::RegisterReceive {
             code_hash,
-            padding: None, // TODO add padding calculation
+            padding: None, // the whole message is space padded by to_cosmos_msg
         }
     }
 
     pub fn redeem(amount: Uint128) -> Self {
         Snip20Msg::Redeem {
             amount,
-            padding: None, // TODO add padding calculation
+            padding: None, // the whole message is space padded by to_cosmos_msg
+        }
+    }
+
+    pub fn batch_transfer(transfers: Vec<(HumanAddr, Uint128)>) -> Self {
+        Snip20Msg::BatchTransfer {
+            actions: transfers.into_iter().map(|(recipient, amount)| TransferAction { recipient, amount, memo: None }).collect(),
+            padding: None,
         }
     }
+
+    pub fn to_cosmos_msg(&self, block_size: usize, callback_code_hash: String, contract_addr: HumanAddr) -> StdResult<CosmosMsg> {
+        let mut msg = to_binary(self)?;
+        space_pad(block_size, &mut msg.0);
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr,
+            callback_code_hash,
+            msg,
+            send: vec![],
+        }))
+    }
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
     GetTriggerer {
-        triggerer: HumanAddr
+        triggerer: HumanAddr,
+        trigger_mode: TriggerMode
     },
     GetUserBets {
         user_bets: Vec<UserBetStruct>
@@ -109,22 +195,47 @@ pub enum QueryAnswer {
         user_bets_total_count: usize
     },
     GetPaginatedRounds { 
-        tier1_rounds: Option<Vec<RoundStruct>>,
-        tier2_rounds: Option<Vec<RoundStruct>>,
-        tier3_rounds: Option<Vec<RoundStruct>>
+        rounds: Vec<RoundStruct>
     },
     GetRounds {
         rounds: Vec<RoundStruct>
     },
     GetTierConfigs { 
-        tier1_configs: Option<TierConfig>,
-        tier2_configs: Option<TierConfig>,
-        tier3_configs: Option<TierConfig>
+        tier_configs: Vec<PoolTierConfig>
+    },
+    GetPools {
+        pools: Vec<PoolInfo>
     },
     CheckTriggers {
-        tier1_trigger: bool,
-        tier2_trigger: bool,
-        tier3_trigger: bool,
+        /// rounds with min_entries, drawable from their closes_at
+        pools: Vec<PoolTrigger>,
+        pending_reveal: Vec<u32>,
+        /// rounds short of min_entries, refunded from their expiry
+        expiring: Vec<PoolTrigger>
+    },
+    VerifyRound {
+        pool: u32,
+        round: u32,
+        seed_hash: Binary,
+        revealed_entropy: String,
+        draw_version: u8,
+        lucky_numbers: Vec<i16>,
+        computed_lucky_numbers: Vec<i16>,
+        winning_ticket: Option<u32>,
+        computed_winning_ticket: Option<u32>,
+        commitment_valid: bool,
+        valid: bool
+    },
+    GetDeposit {
+        pool: u32,
+        deposit: Uint128
+    },
+    GetTreasury {
+        treasury: Option<HumanAddr>,
+        /// house fees not withdrawn yet
+        accrued: Uint128,
+        total_collected: Uint128,
+        total_withdrawn: Uint128
     }
 }
 
@@ -140,6 +251,44 @@ pub enum HandleAnswer {
         message: Option<String>,
     },
     ViewingKey { key: String },
+    Bet { pool: u32, round: u32, ticket: u32 },
+    /// no-loss pool deposit balance after a deposit or a withdraw
+    Deposit { pool: u32, deposit: Uint128 },
+    CreatePool { pool: u32 },
+    CommitEntropy { pools: Vec<u32>, cancelled: Vec<u32> },
+    TriggerLuckyNumber { pools: Vec<PoolDrawSummary> },
+    ClaimAll {
+        claimed_bets: u32,
+        amount: Uint128,
+        /// start index for the next ClaimAll, none when every bet was scanned
+        next_start: Option<u32>
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolTrigger {
+    pub pool: u32,
+    /// block time in seconds
+    pub time: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolDrawSummary {
+    pub pool: u32,
+    pub round: u32,
+    pub lucky_numbers: Vec<i16>,
+    /// drawn ticket of raffle pools
+    pub winning_ticket: Option<u32>,
+    pub winner_tickets_count: u32,
+    pub prize_pool_size: Uint128,
+    pub triggerer_fee: Uint128,
+    pub house_fee: Uint128,
+    /// pool size moved to the next round when nobody won
+    pub next_round_pool_size: Uint128,
+    /// carry over above the jackpot cap accrued on the treasury or split as secondary prize
+    pub overflow_pool_size: Uint128,
+    /// winners were paid on the draw, otherwise they claim with Withdrawl
+    pub prizes_distributed: bool,
 }
 /// success or failure response
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
@@ -147,10 +296,81 @@ pub enum ResponseStatus {
     Success,
     Failure,
 }
-#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct TierConfig {
     pub entry_fee: Uint128,
-    pub triggerer_fee: Uint128,
+    pub triggerer_fee_bps: u16,
+    pub min_triggerer_fee: Option<Uint128>,
+    pub max_triggerer_fee: Option<Uint128>,
     pub min_entries: i16,
-    pub max_rand_number: i16
+    pub max_rand_number: i16,
+    pub round_duration: u64,
+    pub betting_cutoff: u64,
+    pub round_expiry: u64,
+    pub auto_distribute: bool,
+    pub max_auto_winners: u32,
+    pub max_tickets_per_user: u32,
+    pub pool_type: PoolType,
+    /// carry over rules of rounds without winners, unbounded when none
+    pub jackpot: Option<JackpotConfig>,
+    pub house_fee_bps: u16
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotConfig {
+    pub max_carry_over: Uint128,
+    pub overflow: JackpotOverflow
+}
+
+/// where the carry over above max_carry_over goes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JackpotOverflow {
+    /// accrued on the contract treasury like the house fees
+    Treasury,
+    /// split between the round tickets that did not win, claimed like any other prize
+    SecondaryPrize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolType {
+    /// players pick one number, the pool is split by the tickets with the lucky number
+    LuckyNumber,
+    /// players pick `picks` distinct numbers and the pool is split in prize brackets by matched numbers
+    Lotto { picks: u8, brackets: Vec<PrizeBracket> },
+    /// players pick no number, the whole pool goes to one ticket drawn among the round tickets
+    Raffle,
+    /// players deposit on the pool and keep their principal across rounds, every entry_fee deposited is a ticket and deposits are at least the entry_fee.
+    /// Only the yield of the contract account on the SNIP-20 yield source is drawn, so each no-loss pool needs its own yield source
+    NoLoss { yield_source: HumanAddr, yield_source_hash: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PrizeBracket {
+    pub matches: u8,
+    /// share of the prize pool in basis points, unwon brackets move to the next round
+    pub share_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolTierConfig {
+    pub pool: u32,
+    pub config: TierConfig
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolMetadata {
+    pub name: String,
+    pub description: String,
+    pub display_order: u32
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PoolInfo {
+    pub pool: u32,
+    pub name: String,
+    pub description: String,
+    pub display_order: u32,
+    pub config: TierConfig
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use bech32::ToBase32;
+use cosmwasm_std::{to_binary, Binary, HumanAddr, Uint128};
+use ripemd160::{Digest, Ripemd160};
+
+use crate::error::{ContractError, ContractResult};
+use crate::rand::sha_256;
+
+pub const BECH32_PREFIX: &str = "secret";
+
+/// SNIP-24 query permit, signed offline by the user wallet
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Permit {
+    pub params: PermitParams,
+    pub signature: PermitSignature,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitParams {
+    pub allowed_tokens: Vec<HumanAddr>,
+    pub permit_name: String,
+    pub chain_id: String,
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PermitSignature {
+    pub pub_key: PubKey,
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct PubKey {
+    /// ignored, only secp256k1 keys are supported
+    pub r#type: String,
+    /// compressed secp256k1 public key
+    pub value: Binary,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Allowance,
+    Balance,
+    History,
+    Owner,
+}
+
+// amino sign doc the wallets sign for a permit, fields are in the order they are serialized
+#[derive(Serialize)]
+struct SignedPermit {
+    account_number: Uint128,
+    chain_id: String,
+    fee: Fee,
+    memo: String,
+    msgs: Vec<PermitMsg>,
+    sequence: Uint128,
+}
+
+#[derive(Serialize)]
+struct Fee {
+    amount: Vec<Coin>,
+    gas: Uint128,
+}
+
+#[derive(Serialize)]
+struct Coin {
+    amount: Uint128,
+    denom: String,
+}
+
+#[derive(Serialize)]
+struct PermitMsg {
+    r#type: String,
+    value: PermitContent,
+}
+
+#[derive(Serialize)]
+struct PermitContent {
+    allowed_tokens: Vec<HumanAddr>,
+    permissions: Vec<Permission>,
+    permit_name: String,
+}
+
+impl Permit {
+    pub fn check_permission(&self, permission: &Permission) -> bool {
+        self.params.permissions.contains(permission)
+    }
+
+    /// Address of the wallet that signed the permit, errors if the signature doesn't match the permit params
+    pub fn signer(&self) -> ContractResult<HumanAddr> {
+        let sign_doc = to_binary(&SignedPermit {
+            account_number: Uint128(0),
+            chain_id: self.params.chain_id.clone(),
+            fee: Fee { amount: vec![Coin { amount: Uint128(0), denom: "uscrt".to_string() }], gas: Uint128(1) },
+            memo: String::new(),
+            msgs: vec![PermitMsg {
+                r#type: "query_permit".to_string(),
+                value: PermitContent {
+                    allowed_tokens: self.params.allowed_tokens.clone(),
+                    permissions: self.params.permissions.clone(),
+                    permit_name: self.params.permit_name.clone(),
+                },
+            }],
+            sequence: Uint128(0),
+        })?;
+
+        let pub_key = self.signature.pub_key.value.as_slice();
+        let public_key = secp256k1::PublicKey::parse_slice(pub_key, Some(secp256k1::PublicKeyFormat::Compressed))
+            .map_err(|_| invalid_permit("invalid public key"))?;
+        let signature = secp256k1::Signature::parse_slice(self.signature.signature.as_slice())
+            .map_err(|_| invalid_permit("invalid signature"))?;
+        let message = secp256k1::Message::parse(&sha_256(sign_doc.as_slice()));
+        if !secp256k1::verify(&message, &signature, &public_key) {
+            return Err(invalid_permit("signature does not match the permit"));
+        }
+
+        // accounts are the ripemd160 of the sha256 of their public key
+        let account = Ripemd160::digest(&sha_256(pub_key));
+        let address = bech32::encode(BECH32_PREFIX, account.to_vec().to_base32())
+            .map_err(|_| invalid_permit("invalid account"))?;
+        Ok(HumanAddr(address))
+    }
+}
+
+fn invalid_permit(reason: &str) -> ContractError {
+    ContractError::InvalidPermit { reason: reason.to_string() }
+}
@@ -1,3 +1,4 @@
+use rand::Rng;
 use rand_chacha::ChaChaRng;
 use rand_core::{RngCore, SeedableRng};
 
@@ -13,6 +14,48 @@ pub fn sha_256(data: &[u8]) -> [u8; 32] {
     result
 }
 
+/// Version of the lucky number draw algorithm, stored on every finished round
+pub const DRAW_VERSION: u8 = 1;
+
+/// Seed of a single pool round draw: sha256(seed_hash || revealed entropy || pool || round)
+pub fn draw_seed(seed_hash: &[u8], entropy: &[u8], pool: u32, round: u32) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(seed_hash);
+    hasher.update(entropy);
+    hasher.update(pool.to_be_bytes());
+    hasher.update(round.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Lucky number in [1, max_number] drawn from a round seed
+pub fn draw_lucky_number(seed: [u8; 32], max_number: i16) -> i16 {
+    let mut rng = ChaChaRng::from_seed(seed);
+    rng.gen_range(1, max_number + 1)
+}
+
+/// `count` distinct lucky numbers in [1, max_number] drawn from a round seed, in draw order
+pub fn draw_lucky_numbers(seed: [u8; 32], max_number: i16, count: u8) -> Vec<i16> {
+    if count == 1 {
+        return vec![draw_lucky_number(seed, max_number)];
+    }
+
+    // partial Fisher-Yates shuffle
+    let mut rng = ChaChaRng::from_seed(seed);
+    let mut numbers: Vec<i16> = (1..=max_number).collect();
+    for i in 0..count as usize {
+        let j = rng.gen_range(i, numbers.len());
+        numbers.swap(i, j);
+    }
+    numbers.truncate(count as usize);
+    numbers
+}
+
+/// Position in [0, tickets_count) of the raffle winner among the round tickets, drawn from a round seed
+pub fn draw_ticket(seed: [u8; 32], tickets_count: u32) -> u32 {
+    let mut rng = ChaChaRng::from_seed(seed);
+    rng.gen_range(0, tickets_count)
+}
+
 pub struct Prng {
     rng: ChaChaRng,
 }
@@ -22,8 +65,8 @@ impl Prng {
         let mut hasher = Sha256::new();
 
         // write input message
-        hasher.update(&seed);
-        hasher.update(&entropy);
+        hasher.update(seed);
+        hasher.update(entropy);
         let hash = hasher.finalize();
 
         let mut hash_bytes = [0u8; 32];
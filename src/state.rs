@@ -1,9 +1,11 @@
-use std::{any::type_name, collections::HashMap};
+use std::any::type_name;
 use schemars::JsonSchema;
 use secret_toolkit::serialization::{Bincode2, Serde};
 use serde::{de::DeserializeOwned, Serialize, Deserialize};
 
-use cosmwasm_std::{CanonicalAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128};
+use cosmwasm_std::{Binary, CanonicalAddr, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128};
+
+use crate::msg::{PoolType, TierConfig};
 
 pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
     storage.set(key, &Bincode2::serialize(value)?);
@@ -34,27 +36,187 @@ pub fn remove<S: Storage>(storage: &mut S, key: &[u8]) {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct RoundStruct {
+    pub pool: u32,
     pub round_number: u32,
     pub pool_size: Uint128,
+    /// pool size moved over from the previous round, it is not refunded when the round is cancelled
+    pub carried_pool_size: Uint128,
+    /// pool size from this round entries, set when the round is drawn or cancelled
+    pub entries_pool_size: Uint128,
+    /// house fee taken on the draw for the treasury
+    pub house_fee: Uint128,
+    /// carry over above the pool jackpot cap
+    pub overflow_pool_size: Uint128,
+    /// share of the overflow for each ticket that did not win
+    pub secondary_prize_per_ticket: Uint128,
+    /// first drawn number, the only one on lucky number pools
     pub lucky_number: Option<i16>,
+    pub lucky_numbers: Vec<i16>,
+    /// lotto prize brackets results, empty on lucky number pools
+    pub brackets: Vec<BracketResult>,
+    /// drawn ticket of raffle rounds, none when nobody bet
+    pub winning_ticket: Option<u32>,
+    /// no-loss rounds winner, paid on the draw
+    pub winner: Option<HumanAddr>,
     pub users_count: u32,
+    pub tickets_count: u32,
+    pub opens_at: u64,
+    pub closes_at: u64,
     pub round_end_timestamp: Option<u64>,
     pub round_end_pool_size: Option<Uint128>,
     pub users_picked_numbers_count: Vec<u32>,
-    pub winner_users_count: Option<u32>,
+    pub winner_tickets_count: Option<u32>,
+    pub entropy_commitment: Option<Binary>,
+    pub commitment_height: Option<u64>,
+    pub committer: Option<HumanAddr>,
+    /// committers that let their commitment expire, the round then only takes commitments from the triggerer
+    pub missed_reveals: Vec<HumanAddr>,
+    /// contract entropy snapshot taken on the commitment, the draw is seeded from it and it stays private until the draw
+    pub seed_hash: Option<Binary>,
+    pub revealed_entropy: Option<String>,
+    pub draw_version: Option<u8>,
+    pub cancelled: bool,
+    /// pool config the round was opened with, tier changes only apply from the next round
+    pub tier_config: TierConfig,
+}
+
+impl RoundStruct {
+    pub fn new(pool: u32, round_number: u32, pool_size: Uint128, tier_config: &TierConfig, opens_at: u64) -> Self {
+        RoundStruct {
+            pool,
+            round_number,
+            lucky_number: None,
+            lucky_numbers: vec![],
+            brackets: vec![],
+            winning_ticket: None,
+            winner: None,
+            users_count: 0,
+            tickets_count: 0,
+            opens_at,
+            closes_at: opens_at + tier_config.round_duration,
+            round_end_timestamp: None,
+            round_end_pool_size: None,
+            pool_size,
+            carried_pool_size: pool_size,
+            entries_pool_size: Uint128(0),
+            house_fee: Uint128(0),
+            overflow_pool_size: Uint128(0),
+            secondary_prize_per_ticket: Uint128(0),
+            users_picked_numbers_count: vec![0; tier_config.max_rand_number as usize],
+            winner_tickets_count: None,
+            entropy_commitment: None,
+            commitment_height: None,
+            committer: None,
+            missed_reveals: vec![],
+            seed_hash: None,
+            revealed_entropy: None,
+            draw_version: None,
+            cancelled: false,
+            tier_config: tier_config.clone()
+        }
+    }
+
+    /// round following this one, no-loss deposits stay on the pool so their tickets move to the next round
+    pub fn next(&self, pool_size: Uint128, tier_config: &TierConfig, opens_at: u64) -> Self {
+        let mut next_round = RoundStruct::new(self.pool, self.round_number + 1, pool_size, tier_config, opens_at);
+        if let PoolType::NoLoss { .. } = tier_config.pool_type {
+            next_round.users_count = self.users_count;
+            next_round.tickets_count = self.tickets_count;
+        }
+        next_round
+    }
+
+    /// rounds need min_entries tickets, a carried over jackpot doesn't count as entries
+    pub fn has_min_entries(&self) -> bool {
+        self.tickets_count >= self.tier_config.min_entries as u32
+    }
+
+    /// bets are accepted until betting_cutoff seconds before the round closes,
+    /// rounds without min_entries keep taking bets until they get them or expire
+    pub fn is_betting_open(&self, time: u64) -> bool {
+        time + self.tier_config.betting_cutoff <= self.closes_at || !self.has_min_entries()
+    }
+
+    pub fn is_closed(&self, time: u64) -> bool {
+        time >= self.closes_at
+    }
+
+    /// rounds are drawn once the lucky number trigger ran on them
+    pub fn is_drawn(&self) -> bool {
+        self.draw_version.is_some()
+    }
+
+    /// round as returned by the queries, without the picked numbers and the seed of a round not drawn yet
+    pub fn public(mut self) -> Self {
+        self.users_picked_numbers_count = vec![];
+        if !self.is_drawn() {
+            self.seed_hash = None;
+        }
+        self
+    }
+
+    /// prize of a ticket on a finished round, tickets that did not win get the secondary prize
+    pub fn ticket_prize(&self, ticket: u32, numbers: &[i16]) -> Uint128 {
+        match self.winning_prize(ticket, numbers) {
+            Uint128(0) => self.secondary_prize_per_ticket,
+            prize => prize
+        }
+    }
+
+    /// lotto tickets get the prize of their matched numbers bracket
+    fn winning_prize(&self, ticket: u32, numbers: &[i16]) -> Uint128 {
+        match self.tier_config.pool_type {
+            PoolType::Raffle | PoolType::NoLoss { .. } => match self.winning_ticket {
+                Some(winning_ticket) if ticket == winning_ticket => self.pool_size,
+                _ => Uint128(0)
+            },
+            PoolType::Lotto { .. } => {
+                let matches = numbers.iter().filter(|number| self.lucky_numbers.contains(number)).count() as u8;
+                self.brackets.iter().find(|bracket| bracket.matches == matches).map_or(Uint128(0), |bracket| bracket.prize_per_ticket)
+            }
+            PoolType::LuckyNumber => match (self.lucky_number, self.winner_tickets_count) {
+                (Some(lucky_number), Some(winner_tickets_count)) if numbers == [lucky_number] && winner_tickets_count > 0 => {
+                    self.pool_size.multiply_ratio(Uint128(1), Uint128(winner_tickets_count as u128))
+                }
+                _ => Uint128(0)
+            }
+        }
+    }
+
+    /// rounds that did not get min_entries are cancelled round_expiry seconds after closing
+    pub fn expires_at(&self) -> u64 {
+        self.closes_at + self.tier_config.round_expiry
+    }
+
+    pub fn is_expired(&self, time: u64) -> bool {
+        !self.is_drawn()
+            && time >= self.expires_at()
+            && self.tickets_count < self.tier_config.min_entries as u32
+    }
 }
   
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct UserBetStruct {
-    pub round_number: u32,
-    pub tier: i8,
-    pub number: i16,
-    pub claimed_reward: bool,
-    pub timestamp: u64
+pub struct BracketResult {
+    pub matches: u8,
+    pub winners: u32,
+    pub prize_per_ticket: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundTicket {
+    pub owner: CanonicalAddr,
+    pub numbers: Vec<i16>,
+    pub withdrawn: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct UserBetsStruct {
-    pub bet_keys: Vec<String>,
-    pub bets: HashMap<String,UserBetStruct>,
+pub struct UserBetStruct {
+    pub round_number: u32,
+    pub pool: u32,
+    pub ticket: u32,
+    pub numbers: Vec<i16>,
+    pub claimed_reward: bool,
+    pub timestamp: u64,
+    /// withdrawn before the draw, refunded and out of the round
+    pub withdrawn: bool
 }